@@ -1,6 +1,6 @@
 #!/bin/bash
 
-for i in {01..18}; do
+for i in {01..18} 24 25; do
     cargo run --bin day$i
 done
 
@@ -1,20 +1,135 @@
-use anyhow::Result;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use anyhow::{bail, Context, Result};
+
+const DEFAULT_TOP: usize = 3;
 
 fn main() -> Result<()> {
-    let input = std::fs::read_to_string("./inputs/day01.txt").unwrap();
-    let mut elves: Vec<usize> = input
-        .split("\n\n")
-        .map(|line| {
-            line.lines()
-                .flat_map(|num| num.parse::<usize>())
-                .sum::<usize>()
-        })
-        .collect();
+    let k = match std::env::args().nth(1) {
+        None => DEFAULT_TOP,
+        Some(k) => k.parse().context("usage: day01 [<number of elves>]")?,
+    };
 
-    elves.sort_by(|a, b| b.cmp(a));
+    let file = File::open("./inputs/day01.txt")?;
+    let top = top_elves(BufReader::new(file), k)?;
 
-    println!("Part 1: {}", elves[0]);
-    println!("Part 2: {}", elves.iter().take(3).sum::<usize>());
+    for (elf, calories) in top.iter() {
+        println!("Elf {}: {}", elf, calories);
+    }
+    println!(
+        "Part 1: {}",
+        top.first().map_or(0, |&(_, calories)| calories)
+    );
+    println!(
+        "Part 2: {}",
+        top.iter().map(|&(_, calories)| calories).sum::<u64>()
+    );
 
     Ok(())
 }
+
+/// The `k` elves carrying the most calories as (elf number, calories), most first.
+fn top_elves<R: BufRead>(reader: R, k: usize) -> Result<Vec<(usize, u64)>> {
+    // Only the k best totals so far are kept, in a min-heap so the weakest of them is the one
+    // to drop when a better elf comes along
+    let mut heap = BinaryHeap::with_capacity(k + 1);
+    let mut elves = 0;
+    let mut current = None;
+
+    let mut finish = |total: Option<u64>, elves: &mut usize| {
+        if let Some(calories) = total {
+            *elves += 1;
+            // Earlier elves win ties
+            heap.push(Reverse((calories, Reverse(*elves))));
+            if heap.len() > k {
+                heap.pop();
+            }
+        }
+    };
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.context("could not read the calorie list")?;
+        let line = line.trim();
+        if line.is_empty() {
+            finish(current.take(), &mut elves);
+            continue;
+        }
+
+        let calories = match line.parse::<u64>() {
+            Ok(calories) => calories,
+            Err(_) => bail!("line {}: {:?} is not a calorie count", i + 1, line),
+        };
+        let total = current.unwrap_or(0u64);
+        current = Some(total.checked_add(calories).with_context(|| {
+            format!(
+                "line {}: elf {} carries too many calories",
+                i + 1,
+                elves + 1
+            )
+        })?);
+    }
+    finish(current, &mut elves);
+
+    Ok(heap
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((calories, Reverse(elf)))| (elf, calories))
+        .collect())
+}
+
+#[allow(dead_code)]
+const TEST_INPUT: &str = r#"1000
+2000
+3000
+
+4000
+
+5000
+6000
+
+7000
+8000
+9000
+
+10000"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parts() {
+        let top = top_elves(TEST_INPUT.as_bytes(), 3).unwrap();
+        assert_eq!(top, vec![(4, 24000), (3, 11000), (5, 10000)]);
+        assert_eq!(top[0].1, 24000);
+        assert_eq!(
+            top.iter().map(|&(_, calories)| calories).sum::<u64>(),
+            45000
+        );
+    }
+
+    #[test]
+    fn test_any_k() {
+        assert_eq!(
+            top_elves(TEST_INPUT.as_bytes(), 1).unwrap(),
+            vec![(4, 24000)]
+        );
+        assert_eq!(top_elves(TEST_INPUT.as_bytes(), 10).unwrap().len(), 5);
+        assert!(top_elves(TEST_INPUT.as_bytes(), 0).unwrap().is_empty());
+        // Extra blank lines don't make empty elves
+        assert_eq!(
+            top_elves("1\n\n\n\n2\n".as_bytes(), 5).unwrap(),
+            vec![(2, 2), (1, 1)]
+        );
+    }
+
+    #[test]
+    fn test_bad_lines() {
+        let error = top_elves("1000\n\n20x0\n".as_bytes(), 3).unwrap_err();
+        assert_eq!(error.to_string(), "line 3: \"20x0\" is not a calorie count");
+        assert!(top_elves("18446744073709551615\n1".as_bytes(), 3).is_err());
+    }
+}
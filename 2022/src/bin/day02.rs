@@ -1,45 +1,310 @@
-use anyhow::Result;
-
-use lazy_static::lazy_static;
-use std::collections::HashMap;
-
-lazy_static! {
-    static ref PLAYS_PART1: HashMap<&'static str, u32> = {
-        let mut m = HashMap::new();
-
-        m.insert("A X", 4u32);
-        m.insert("A Y", 8u32);
-        m.insert("A Z", 3u32);
-        m.insert("B X", 1u32);
-        m.insert("B Y", 5u32);
-        m.insert("B Z", 9u32);
-        m.insert("C X", 7u32);
-        m.insert("C Y", 2u32);
-        m.insert("C Z", 6u32);
-        m
-    };
-    static ref PLAYS_PART2: HashMap<&'static str, u32> = {
-        let mut m = HashMap::new();
-
-        m.insert("A X", 3u32);
-        m.insert("A Y", 4u32);
-        m.insert("A Z", 8u32);
-        m.insert("B X", 1u32);
-        m.insert("B Y", 5u32);
-        m.insert("B Z", 9u32);
-        m.insert("C X", 2u32);
-        m.insert("C Y", 6u32);
-        m.insert("C Z", 7u32);
-        m
-    };
+use anyhow::{anyhow, bail, Result};
+
+// The shapes in an order where each one beats the shape before it, and every shape beats
+// exactly the shapes an odd number of places before it (wrapping around)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Rock,
+    Paper,
+    Scissors,
+    Spock,
+    Lizard,
+}
+
+const SHAPES: [Shape; 5] = [
+    Shape::Rock,
+    Shape::Paper,
+    Shape::Scissors,
+    Shape::Spock,
+    Shape::Lizard,
+];
+
+// Letters for our responses; the classic game uses the first three
+const RESPONSES: [char; 5] = ['X', 'Y', 'Z', 'W', 'V'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Loss,
+    Draw,
+    Win,
+}
+
+impl Outcome {
+    fn score(self) -> u32 {
+        match self {
+            Outcome::Loss => 0,
+            Outcome::Draw => 3,
+            Outcome::Win => 6,
+        }
+    }
+}
+
+impl Shape {
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn score(self) -> u32 {
+        self.index() as u32 + 1
+    }
+}
+
+/*
+With the shapes in a circle, a shape beats the ones an odd distance behind it and loses to the
+ones an even distance behind it. That reproduces rock-paper-scissors with three shapes and
+Rock-Paper-Scissors-Spock-Lizard with five, so no table of matchups is needed.
+*/
+#[derive(Debug, Clone, Copy)]
+struct Game {
+    size: usize,
+}
+
+impl Game {
+    const CLASSIC: Game = Game { size: 3 };
+    const EXTENDED: Game = Game { size: 5 };
+
+    fn shapes(&self) -> &'static [Shape] {
+        &SHAPES[..self.size]
+    }
+
+    fn outcome(&self, me: Shape, opponent: Shape) -> Outcome {
+        let distance = (me.index() + self.size - opponent.index()) % self.size;
+        match distance {
+            0 => Outcome::Draw,
+            d if d % 2 == 1 => Outcome::Win,
+            _ => Outcome::Loss,
+        }
+    }
+
+    fn score(&self, me: Shape, opponent: Shape) -> u32 {
+        me.score() + self.outcome(me, opponent).score()
+    }
+
+    fn parse_round(&self, line: &str) -> Result<(Shape, usize)> {
+        let letters = line.chars().collect::<Vec<_>>();
+        let (opponent, response) = match letters.as_slice() {
+            [opponent, ' ', response] => (*opponent, *response),
+            _ => bail!("malformed round {:?}", line),
+        };
+
+        let opponent = self
+            .shapes()
+            .get((opponent as usize).wrapping_sub('A' as usize))
+            .ok_or_else(|| anyhow!("unknown opponent shape {:?}", opponent))?;
+        let response = RESPONSES[..self.size]
+            .iter()
+            .position(|&r| r == response)
+            .ok_or_else(|| anyhow!("unknown response {:?}", response))?;
+        Ok((*opponent, response))
+    }
+}
+
+/// One way of reading the second column of the strategy guide.
+trait Strategy {
+    fn name(&self) -> String;
+
+    fn respond(&self, game: &Game, opponent: Shape, response: usize) -> Result<Shape>;
+}
+
+// Each response letter names the shape to play
+struct ShapeStrategy {
+    shapes: Vec<Shape>,
+}
+
+impl Strategy for ShapeStrategy {
+    fn name(&self) -> String {
+        self.shapes
+            .iter()
+            .zip(RESPONSES)
+            .map(|(shape, letter)| format!("{}={:?}", letter, shape))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn respond(&self, _game: &Game, _opponent: Shape, response: usize) -> Result<Shape> {
+        Ok(self.shapes[response])
+    }
+}
+
+// X, Y and Z say to lose, draw or win, picking the highest scoring shape that does it
+struct OutcomeStrategy;
+
+impl Strategy for OutcomeStrategy {
+    fn name(&self) -> String {
+        "X=lose Y=draw Z=win".to_string()
+    }
+
+    fn respond(&self, game: &Game, opponent: Shape, response: usize) -> Result<Shape> {
+        let outcome = match response {
+            0 => Outcome::Loss,
+            1 => Outcome::Draw,
+            2 => Outcome::Win,
+            _ => bail!("{} doesn't name an outcome", RESPONSES[response]),
+        };
+        let shape = game
+            .shapes()
+            .iter()
+            .filter(|&&shape| game.outcome(shape, opponent) == outcome)
+            .max_by_key(|shape| shape.score())
+            .unwrap();
+        Ok(*shape)
+    }
+}
+
+fn total(game: &Game, rounds: &[(Shape, usize)], strategy: &dyn Strategy) -> Result<u32> {
+    rounds
+        .iter()
+        .map(|&(opponent, response)| {
+            let me = strategy.respond(game, opponent, response)?;
+            Ok(game.score(me, opponent))
+        })
+        .sum()
+}
+
+// Every order of `shapes`
+fn permutations(shapes: &[Shape]) -> Vec<Vec<Shape>> {
+    if shapes.is_empty() {
+        return vec![vec![]];
+    }
+    let mut all = vec![];
+    for (i, &first) in shapes.iter().enumerate() {
+        let mut rest = shapes.to_vec();
+        rest.remove(i);
+        for mut tail in permutations(&rest) {
+            tail.insert(0, first);
+            all.push(tail);
+        }
+    }
+    all
+}
+
+/// The reading of the response letters that scores the most, and its total.
+fn best_interpretation(game: &Game, rounds: &[(Shape, usize)]) -> Result<(String, u32)> {
+    let mut strategies: Vec<Box<dyn Strategy>> = permutations(game.shapes())
+        .into_iter()
+        .map(|shapes| Box::new(ShapeStrategy { shapes }) as Box<dyn Strategy>)
+        .collect();
+    strategies.push(Box::new(OutcomeStrategy));
+
+    let mut best: Option<(String, u32)> = None;
+    for strategy in strategies {
+        // The outcome reading doesn't cover every letter of the extended game
+        let Ok(score) = total(game, rounds, strategy.as_ref()) else {
+            continue;
+        };
+        if best.as_ref().is_none_or(|(_, top)| score > *top) {
+            best = Some((strategy.name(), score));
+        }
+    }
+    Ok(best.unwrap())
 }
 
 fn main() -> Result<()> {
-    let input = std::fs::read_to_string("./inputs/day02.txt").unwrap();
-    let score = input.lines().flat_map(|x| PLAYS_PART1.get(x)).sum::<u32>();
-    println!("Part 1: {}", score);
+    let game = match std::env::args().nth(1).as_deref() {
+        None => Game::CLASSIC,
+        Some("--extended") => Game::EXTENDED,
+        Some(_) => bail!("usage: day02 [--extended]"),
+    };
 
-    let score = input.lines().flat_map(|x| PLAYS_PART2.get(x)).sum::<u32>();
-    println!("Part 2: {}", score);
+    let input = std::fs::read_to_string("./inputs/day02.txt")?;
+    let rounds = input
+        .lines()
+        .map(|line| game.parse_round(line))
+        .collect::<Result<Vec<_>>>()?;
+
+    let as_shapes = ShapeStrategy {
+        shapes: game.shapes().to_vec(),
+    };
+    println!("Part 1: {}", total(&game, &rounds, &as_shapes)?);
+    if game.size == Game::CLASSIC.size {
+        println!("Part 2: {}", total(&game, &rounds, &OutcomeStrategy)?);
+    }
+
+    let (name, score) = best_interpretation(&game, &rounds)?;
+    println!("Best reading: {} scores {}", name, score);
     Ok(())
 }
+
+#[allow(dead_code)]
+const TEST_INPUT: &str = r#"A Y
+B X
+C Z"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rounds(game: &Game, input: &str) -> Vec<(Shape, usize)> {
+        input
+            .lines()
+            .map(|line| game.parse_round(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_parts() {
+        let game = Game::CLASSIC;
+        let rounds = rounds(&game, TEST_INPUT);
+        let as_shapes = ShapeStrategy {
+            shapes: game.shapes().to_vec(),
+        };
+        assert_eq!(total(&game, &rounds, &as_shapes).unwrap(), 15);
+        assert_eq!(total(&game, &rounds, &OutcomeStrategy).unwrap(), 12);
+    }
+
+    #[test]
+    fn test_best_interpretation() {
+        let game = Game::CLASSIC;
+        let rounds = rounds(&game, TEST_INPUT);
+        // Answering Rock, Paper and Scissors with Paper, Scissors and Rock wins every round
+        assert_eq!(
+            best_interpretation(&game, &rounds).unwrap(),
+            ("X=Scissors Y=Paper Z=Rock".to_string(), 24)
+        );
+    }
+
+    #[test]
+    fn test_extended_rules() {
+        use Shape::*;
+        let game = Game::EXTENDED;
+        for (winner, loser) in [
+            (Scissors, Paper),
+            (Paper, Rock),
+            (Rock, Lizard),
+            (Lizard, Spock),
+            (Spock, Scissors),
+            (Scissors, Lizard),
+            (Lizard, Paper),
+            (Paper, Spock),
+            (Spock, Rock),
+            (Rock, Scissors),
+        ] {
+            assert_eq!(game.outcome(winner, loser), Outcome::Win);
+            assert_eq!(game.outcome(loser, winner), Outcome::Loss);
+        }
+
+        let rounds = rounds(&game, "E X\nD X\nA Z");
+        assert_eq!(
+            total(&game, &rounds, &OutcomeStrategy).unwrap(),
+            // Lose to Lizard with Spock rather than Paper, lose to Spock with Scissors rather
+            // than Rock, and beat Rock with Spock rather than Paper
+            4 + 3 + (4 + 6)
+        );
+
+        // Only the shape reading covers the extra response letters
+        let rounds = vec![game.parse_round("E V").unwrap()];
+        assert!(total(&game, &rounds, &OutcomeStrategy).is_err());
+        let as_shapes = ShapeStrategy {
+            shapes: game.shapes().to_vec(),
+        };
+        assert_eq!(total(&game, &rounds, &as_shapes).unwrap(), 5 + 3);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Game::CLASSIC.parse_round("D X").is_err());
+        assert!(Game::CLASSIC.parse_round("A W").is_err());
+        assert!(Game::CLASSIC.parse_round("AX").is_err());
+        assert!(Game::EXTENDED.parse_round("E V").is_ok());
+    }
+}
@@ -1,69 +1,203 @@
-use std::collections::HashSet;
+use std::str::FromStr;
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 
-pub fn to_priority(c: char) -> u32 {
-    if c.is_lowercase() {
-        c as u32 - 'a' as u32 + 1
-    } else {
-        c as u32 - 'A' as u32 + 27
+const DEFAULT_GROUP_SIZE: usize = 3;
+
+pub fn to_priority(c: char) -> Result<u32> {
+    match c {
+        'a'..='z' => Ok(c as u32 - 'a' as u32 + 1),
+        'A'..='Z' => Ok(c as u32 - 'A' as u32 + 27),
+        _ => bail!("invalid item {:?}", c),
+    }
+}
+
+fn from_priority(priority: u32) -> char {
+    match priority {
+        1..=26 => (b'a' + priority as u8 - 1) as char,
+        _ => (b'A' + priority as u8 - 27) as char,
     }
 }
 
+// Every priority fits in a u64, so a set of items is a bitmask with bit p set for priority p
+fn items(s: &str) -> Result<u64> {
+    s.chars()
+        .try_fold(0u64, |set, c| Ok(set | 1 << to_priority(c)?))
+}
+
+fn priorities(set: u64) -> impl Iterator<Item = u32> {
+    (1..=52).filter(move |p| set & 1 << p != 0)
+}
+
+fn describe(set: u64) -> String {
+    priorities(set).map(from_priority).collect()
+}
+
+#[derive(Debug)]
+struct Rucksack {
+    left: u64,
+    right: u64,
+}
+
+impl Rucksack {
+    fn all(&self) -> u64 {
+        self.left | self.right
+    }
+
+    fn shared(&self) -> u64 {
+        self.left & self.right
+    }
+}
+
+impl FromStr for Rucksack {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_ascii() {
+            bail!("rucksack {:?} holds items that aren't ASCII letters", s);
+        }
+        if !s.len().is_multiple_of(2) {
+            bail!("rucksack {:?} can't be split into equal compartments", s);
+        }
+        let (left, right) = s.split_at(s.len() / 2);
+        Ok(Rucksack {
+            left: items(left)?,
+            right: items(right)?,
+        })
+    }
+}
+
+fn parse_rucksacks(input: &str) -> Result<Vec<Rucksack>> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            line.parse::<Rucksack>()
+                .with_context(|| format!("line {}", i + 1))
+        })
+        .collect()
+}
+
 fn main() -> Result<()> {
+    let group_size = match std::env::args().nth(1) {
+        None => DEFAULT_GROUP_SIZE,
+        Some(size) => size.parse()?,
+    };
+
     let input = std::fs::read_to_string("./inputs/day03.txt")?;
-    let rucksacks = input.lines().collect();
+    let rucksacks = parse_rucksacks(&input)?;
+
+    // Every rucksack should have exactly one item in both compartments
+    for (line, shared) in anomalies(&rucksacks) {
+        match shared {
+            0 => eprintln!("line {}: no item is in both compartments", line),
+            _ => eprintln!(
+                "line {}: {} are all in both compartments",
+                line,
+                describe(shared)
+            ),
+        }
+    }
 
     println!("Part 1: {:?}", part1(&rucksacks));
 
-    println!("Part 2: {:?}", part2(&rucksacks));
+    println!("Part 2: {:?}", part2(&rucksacks, group_size)?);
 
     Ok(())
 }
 
-fn part1(rucksacks: &Vec<&str>) -> u32 {
-    let priority_sums = rucksacks
+fn part1(rucksacks: &[Rucksack]) -> u32 {
+    rucksacks
         .iter()
-        .map(|rucksack| {
-            let half = rucksack.len() / 2;
-            let (a, b) = rucksack.split_at(half);
-            let a = a.chars().collect::<HashSet<_>>();
-            b.chars()
-                .find_map(|c| {
-                    if a.contains(&c) {
-                        Some(to_priority(c))
-                    } else {
-                        None
-                    }
-                })
-                .unwrap_or(0)
-        })
-        .sum::<u32>();
-    priority_sums
+        .flat_map(|rucksack| priorities(rucksack.shared()))
+        .sum()
 }
 
-fn part2(rucksacks: &Vec<&str>) -> u32 {
-    let badge_sums = rucksacks
-        .chunks(3)
-        .map(|group| find_group_badge(group))
-        .sum::<u32>();
+/// Line numbers of rucksacks without exactly one shared item, with the items they do share.
+fn anomalies(rucksacks: &[Rucksack]) -> Vec<(usize, u64)> {
+    rucksacks
+        .iter()
+        .enumerate()
+        .filter(|(_, rucksack)| rucksack.shared().count_ones() != 1)
+        .map(|(i, rucksack)| (i + 1, rucksack.shared()))
+        .collect()
+}
 
-    badge_sums
+fn part2(rucksacks: &[Rucksack], group_size: usize) -> Result<u32> {
+    if group_size == 0 || !rucksacks.len().is_multiple_of(group_size) {
+        bail!(
+            "{} rucksacks can't be split into groups of {}",
+            rucksacks.len(),
+            group_size
+        );
+    }
+
+    rucksacks
+        .chunks(group_size)
+        .enumerate()
+        .map(|(i, group)| find_group_badge(group).with_context(|| format!("group {}", i + 1)))
+        .sum()
 }
 
-fn find_group_badge(group: &[&str]) -> u32 {
-    let a = group[0].chars().collect::<HashSet<_>>();
-    let b = group[1].chars().collect::<HashSet<_>>();
-    let badge = group[2]
-        .chars()
-        .find_map(|c| {
-            if a.contains(&c) && b.contains(&c) {
-                Some(to_priority(c))
-            } else {
-                None
-            }
-        })
-        .unwrap();
+fn find_group_badge(group: &[Rucksack]) -> Result<u32> {
+    let common = group
+        .iter()
+        .fold(u64::MAX, |common, rucksack| common & rucksack.all());
+
+    match common.count_ones() {
+        1 => Ok(common.trailing_zeros()),
+        0 => bail!("no item is carried by every elf"),
+        _ => bail!("{} are all carried by every elf", describe(common)),
+    }
+}
+
+#[allow(dead_code)]
+const TEST_INPUT: &str = r#"vJrwpWtwJgWrhcsFMMfFFhFp
+jqHRNqRjqzjGDLGLrsFMfFZSrLrFZsSL
+PmmdzqPrVvPwwTWBwg
+wMqvLMZHhHMvwLHjbvcjnnSBnvTQFn
+ttgJtRGJQctTZtZT
+CrZsJsPPZsGzwwsLwLmpwMDw"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    badge
+    #[test]
+    fn test_parts() {
+        let rucksacks = parse_rucksacks(TEST_INPUT).unwrap();
+        assert_eq!(part1(&rucksacks), 157);
+        assert_eq!(part2(&rucksacks, 3).unwrap(), 70);
+        assert!(anomalies(&rucksacks).is_empty());
+    }
+
+    #[test]
+    fn test_group_sizes() {
+        let rucksacks = parse_rucksacks(TEST_INPUT).unwrap();
+        // Pairs of the example rucksacks share more than one item
+        assert!(part2(&rucksacks, 2).is_err());
+        // A lone rucksack has every one of its items in common with itself
+        assert!(format!("{:#}", part2(&rucksacks, 1).unwrap_err())
+            .starts_with("group 1: cfghprstvwFJMW are all carried"));
+        assert!(part2(&rucksacks, 4).is_err());
+        assert!(part2(&rucksacks, 0).is_err());
+        assert_eq!(part2(&rucksacks[..3], 3).unwrap(), 18);
+    }
+
+    #[test]
+    fn test_anomalies() {
+        let rucksacks = parse_rucksacks("abcd\nabab\naAbA").unwrap();
+        assert_eq!(
+            anomalies(&rucksacks)
+                .into_iter()
+                .map(|(line, shared)| (line, describe(shared)))
+                .collect::<Vec<_>>(),
+            vec![(1, "".to_string()), (2, "ab".to_string())]
+        );
+        assert!(parse_rucksacks("abc").is_err());
+        assert!(parse_rucksacks("a1").is_err());
+        // The midpoint of these bytes falls inside the 'é'
+        assert!(parse_rucksacks("aéb").is_err());
+        assert!(format!("{:#}", parse_rucksacks("ab\nabc").unwrap_err()).starts_with("line 2: "));
+    }
 }
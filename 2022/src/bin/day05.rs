@@ -1,90 +1,425 @@
-use std::{collections::VecDeque, str::FromStr};
+use std::fmt;
+use std::str::FromStr;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 
 #[derive(Debug)]
 struct Procedure {
-    num_move: usize,
-    from: usize,
-    to: usize,
+    count: usize,
+    // Stacks are referred to by their label in the drawing
+    from: String,
+    to: String,
 }
 
-const MOVE_INDEX: usize = 1;
-const FROM_INDEX: usize = 3;
-const TO_INDEX: usize = 5;
-
 impl FromStr for Procedure {
     type Err = anyhow::Error;
 
+    // move 1 from 2 to 1
     fn from_str(s: &str) -> Result<Self> {
-        let parts = s.split_whitespace().collect::<Vec<_>>();
-        let num_move = parts[MOVE_INDEX].parse()?;
-        let from = parts[FROM_INDEX].parse::<usize>()? - 1;
-        let to = parts[TO_INDEX].parse::<usize>()? - 1;
+        match s.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["move", count, "from", from, "to", to] => Ok(Procedure {
+                count: count
+                    .parse()
+                    .with_context(|| format!("invalid crate count in {:?}", s))?,
+                from: from.to_string(),
+                to: to.to_string(),
+            }),
+            _ => bail!("malformed procedure {:?}", s),
+        }
+    }
+}
 
-        Ok(Procedure { num_move, from, to })
+impl fmt::Display for Procedure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.count, self.from, self.to)
     }
 }
 
-fn main() -> Result<()> {
-    let input = std::fs::read_to_string("./inputs/day05.txt").unwrap();
+/// A model of crane, which decides what order crates land in when several are moved.
+trait Crane {
+    fn name(&self) -> String;
 
-    let (starting_stacks, procedures) = input.split_once("\n\n").unwrap();
+    /// Move the top `count` crates of `from` onto `to`, which has already been checked to hold
+    /// at least that many.
+    fn transfer(&self, from: &mut Vec<char>, to: &mut Vec<char>, count: usize);
+}
 
-    let mut stacks: Vec<Vec<char>> = Vec::new();
+// Lifts one crate at a time, so the moved crates end up in reverse order
+struct CrateMover9000;
 
-    for (i, level) in starting_stacks.lines().rev().enumerate() {
-        if i == 0 {
-            level
-                .split_ascii_whitespace()
-                .for_each(|_| stacks.push(Vec::new()));
-        } else {
-            level.chars().enumerate().for_each(|(i, c)| {
-                if c.is_ascii_alphabetic() {
-                    stacks[i / 4].push(c);
-                }
-            });
+impl Crane for CrateMover9000 {
+    fn name(&self) -> String {
+        "CrateMover 9000".to_string()
+    }
+
+    fn transfer(&self, from: &mut Vec<char>, to: &mut Vec<char>, count: usize) {
+        let start = from.len() - count;
+        to.extend(from.drain(start..).rev());
+    }
+}
+
+// Lifts all the crates at once, keeping their order
+struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn name(&self) -> String {
+        "CrateMover 9001".to_string()
+    }
+
+    fn transfer(&self, from: &mut Vec<char>, to: &mut Vec<char>, count: usize) {
+        let start = from.len() - count;
+        to.extend(from.drain(start..));
+    }
+}
+
+// Lifts up to `capacity` crates at once, keeping the order within each lift
+struct LimitedCrane {
+    capacity: usize,
+}
+
+impl Crane for LimitedCrane {
+    fn name(&self) -> String {
+        format!("crane lifting {} at a time", self.capacity)
+    }
+
+    fn transfer(&self, from: &mut Vec<char>, to: &mut Vec<char>, count: usize) {
+        let mut left = count;
+        while left > 0 {
+            let lift = left.min(self.capacity);
+            let start = from.len() - lift;
+            to.extend(from.drain(start..));
+            left -= lift;
+        }
+    }
+}
+
+/*
+Stacks are found from the label line rather than assuming a crate every four columns, so labels
+can be any width and any distance apart. Enough of the drawing's layout is kept to print it
+back out exactly as it was read.
+*/
+#[derive(Debug, Clone)]
+struct Stacks {
+    // Bottom crate first
+    stacks: Vec<Vec<char>>,
+    labels: Vec<String>,
+    label_line: String,
+    // The column each stack's crate letters are drawn in
+    columns: Vec<usize>,
+    // Set when every line of the drawing was padded with spaces to the same width
+    width: Option<usize>,
+}
+
+impl Stacks {
+    fn index(&self, label: &str) -> Result<usize> {
+        self.labels
+            .iter()
+            .position(|l| l == label)
+            .ok_or_else(|| anyhow!("no stack labelled {}", label))
+    }
+
+    fn apply(&mut self, crane: &dyn Crane, procedure: &Procedure) -> Result<()> {
+        let from = self.index(&procedure.from)?;
+        let to = self.index(&procedure.to)?;
+        if self.stacks[from].len() < procedure.count {
+            bail!(
+                "can't {}, stack {} only has {} crates",
+                procedure,
+                procedure.from,
+                self.stacks[from].len()
+            );
+        }
+        if from == to {
+            // Whatever the crane, the stack ends up as it started
+            return Ok(());
         }
+
+        let mut source = std::mem::take(&mut self.stacks[from]);
+        crane.transfer(&mut source, &mut self.stacks[to], procedure.count);
+        self.stacks[from] = source;
+        Ok(())
+    }
+
+    fn tops(&self) -> String {
+        self.stacks.iter().flat_map(|stack| stack.last()).collect()
     }
+}
+
+impl FromStr for Stacks {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut lines = s.lines().collect::<Vec<_>>();
+        let label_line = lines
+            .pop()
+            .ok_or_else(|| anyhow!("missing the stack labels"))?;
+
+        // Each label with the span of columns it covers
+        let mut labels = vec![];
+        let mut spans = vec![];
+        let mut start = None;
+        // Columns are counted in chars, the same as for the crates
+        let label_chars = label_line.chars().collect::<Vec<_>>();
+        for (col, &c) in label_chars.iter().chain([&' ']).enumerate() {
+            match (c.is_whitespace(), start) {
+                (false, None) => start = Some(col),
+                (true, Some(from)) => {
+                    labels.push(label_chars[from..col].iter().collect());
+                    spans.push((from, col - 1));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if labels.is_empty() {
+            bail!("no stack labels in {:?}", label_line);
+        }
 
-    // Save starting crate stacks for use in part 2
-    let mut part2_stacks = stacks.clone();
+        let mut columns = vec![None; labels.len()];
+        let mut stacks = vec![vec![]; labels.len()];
+        for (i, line) in lines.iter().rev().enumerate() {
+            let row = lines.len() - i;
+            let chars = line.chars().collect::<Vec<_>>();
+            let mut col = 0;
+            while col < chars.len() {
+                match chars[col..] {
+                    [' ', ..] => col += 1,
+                    ['[', c, ']', ..] if !c.is_whitespace() => {
+                        // The label whose span is nearest to the crate letter
+                        let letter = col + 1;
+                        let stack = (0..spans.len())
+                            .min_by_key(|&s| {
+                                let (from, to) = spans[s];
+                                from.saturating_sub(letter) + letter.saturating_sub(to)
+                            })
+                            .unwrap();
 
-    procedures.lines().for_each(|line| {
-        let procedure = line.parse::<Procedure>().unwrap();
-        for _ in 0..procedure.num_move {
-            let from = stacks[procedure.from].pop().unwrap();
-            stacks[procedure.to].push(from);
+                        if *columns[stack].get_or_insert(letter) != letter {
+                            bail!(
+                                "crate on line {} at column {} is not lined up with stack {}",
+                                row,
+                                letter + 1,
+                                labels[stack]
+                            );
+                        }
+                        if stacks[stack].len() != i {
+                            bail!(
+                                "crate on line {} of stack {} is floating",
+                                row,
+                                labels[stack]
+                            );
+                        }
+                        stacks[stack].push(c);
+                        col += 3;
+                    }
+                    _ => bail!(
+                        "unexpected {:?} on line {} at column {}",
+                        chars[col],
+                        row,
+                        col + 1
+                    ),
+                }
+            }
         }
-    });
 
-    let part1 = stacks
-        .iter()
-        .peekable()
-        .flat_map(|stack| stack.last())
-        .collect::<String>();
+        // Empty stacks are drawn under the middle of their label
+        let columns = columns
+            .iter()
+            .zip(spans.iter())
+            .map(|(&column, &(from, to))| column.unwrap_or(((from + to) / 2).max(1)))
+            .collect();
 
-    println!("Part 1: {}", part1);
+        let width = label_line.chars().count();
+        let padded = !lines.is_empty()
+            && lines.iter().all(|line| line.chars().count() == width)
+            && s.lines().any(|line| line.ends_with(' '));
+
+        Ok(Stacks {
+            stacks,
+            labels,
+            label_line: label_line.to_string(),
+            columns,
+            width: padded.then_some(width),
+        })
+    }
+}
 
-    // Part 2
+impl fmt::Display for Stacks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let height = self
+            .stacks
+            .iter()
+            .map(|stack| stack.len())
+            .max()
+            .unwrap_or(0);
+        let needed = self.columns.iter().max().unwrap() + 2;
 
-    procedures.lines().for_each(|line| {
-        let procedure = line.parse::<Procedure>().unwrap();
-        let mut temp = VecDeque::<char>::new();
-        for _ in 0..procedure.num_move {
-            let from = part2_stacks[procedure.from].pop().unwrap();
-            temp.push_front(from);
+        for level in (0..height).rev() {
+            let mut line = vec![' '; needed.max(self.width.unwrap_or(0))];
+            for (stack, &col) in self.stacks.iter().zip(self.columns.iter()) {
+                if let Some(&c) = stack.get(level) {
+                    line[col - 1..=col + 1].copy_from_slice(&['[', c, ']']);
+                }
+            }
+
+            let line = line.into_iter().collect::<String>();
+            match self.width {
+                Some(_) => writeln!(f, "{}", line)?,
+                None => writeln!(f, "{}", line.trim_end())?,
+            }
         }
-        part2_stacks[procedure.to].append(&mut temp.into());
-    });
+        write!(f, "{}", self.label_line)
+    }
+}
 
-    let part2 = part2_stacks
-        .iter()
-        .peekable()
-        .flat_map(|stack| stack.last())
-        .collect::<String>();
+fn parse_input(input: &str) -> Result<(Stacks, Vec<Procedure>)> {
+    let (drawing, procedures) = input
+        .split_once("\n\n")
+        .ok_or_else(|| anyhow!("missing the blank line after the drawing"))?;
+    let stacks = drawing.parse::<Stacks>()?;
+    let procedures = procedures
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            line.parse::<Procedure>()
+                .with_context(|| format!("invalid procedure {}", i + 1))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok((stacks, procedures))
+}
 
-    println!("Part 2: {}", part2);
+/// Run every procedure with `crane`, optionally printing the stacks after each move.
+fn rearrange(
+    stacks: &Stacks,
+    procedures: &[Procedure],
+    crane: &dyn Crane,
+    steps: bool,
+) -> Result<Stacks> {
+    let mut stacks = stacks.clone();
+    if steps {
+        println!("{}:\n{}\n", crane.name(), stacks);
+    }
+    for (i, procedure) in procedures.iter().enumerate() {
+        stacks
+            .apply(crane, procedure)
+            .with_context(|| format!("procedure {} failed", i + 1))?;
+        if steps {
+            println!("{}\n{}\n", procedure, stacks);
+        }
+    }
+    Ok(stacks)
+}
+
+fn main() -> Result<()> {
+    let mut steps = false;
+    let mut crane: Option<Box<dyn Crane>> = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--steps" => steps = true,
+            "--crane" => {
+                crane = Some(match args.next().as_deref() {
+                    Some("9000") => Box::new(CrateMover9000),
+                    Some("9001") => Box::new(CrateMover9001),
+                    Some(capacity) => match capacity.parse()? {
+                        0 => bail!("a crane has to lift at least one crate"),
+                        capacity => Box::new(LimitedCrane { capacity }),
+                    },
+                    None => bail!("--crane needs 9000, 9001 or a lift capacity"),
+                })
+            }
+            _ => bail!("usage: day05 [--steps] [--crane 9000 | 9001 | <capacity>]"),
+        }
+    }
+
+    let input = std::fs::read_to_string("./inputs/day05.txt")?;
+    let (stacks, procedures) = parse_input(&input)?;
+
+    if let Some(crane) = crane {
+        let stacks = rearrange(&stacks, &procedures, crane.as_ref(), steps)?;
+        println!("{}: {}", crane.name(), stacks.tops());
+        return Ok(());
+    }
+
+    let part1 = rearrange(&stacks, &procedures, &CrateMover9000, steps)?;
+    println!("Part 1: {}", part1.tops());
+
+    let part2 = rearrange(&stacks, &procedures, &CrateMover9001, steps)?;
+    println!("Part 2: {}", part2.tops());
 
     Ok(())
 }
+
+#[allow(dead_code)]
+const TEST_INPUT: &str = r#"    [D]    
+[N] [C]    
+[Z] [M] [P]
+ 1   2   3 
+
+move 1 from 2 to 1
+move 3 from 1 to 3
+move 2 from 2 to 1
+move 1 from 1 to 2"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parts() {
+        let (stacks, procedures) = parse_input(TEST_INPUT).unwrap();
+        let part1 = rearrange(&stacks, &procedures, &CrateMover9000, false).unwrap();
+        assert_eq!(part1.tops(), "CMZ");
+        let part2 = rearrange(&stacks, &procedures, &CrateMover9001, false).unwrap();
+        assert_eq!(part2.tops(), "MCD");
+    }
+
+    #[test]
+    fn test_limited_crane() {
+        let (stacks, procedures) = parse_input(TEST_INPUT).unwrap();
+        let one = rearrange(&stacks, &procedures, &LimitedCrane { capacity: 1 }, false).unwrap();
+        assert_eq!(one.tops(), "CMZ");
+        let two = rearrange(&stacks, &procedures, &LimitedCrane { capacity: 2 }, false).unwrap();
+        assert_eq!(two.stacks[2], vec!['P', 'N', 'D', 'Z']);
+    }
+
+    #[test]
+    fn test_drawing_round_trips() {
+        let (drawing, _) = TEST_INPUT.split_once("\n\n").unwrap();
+        assert_eq!(drawing.parse::<Stacks>().unwrap().to_string(), drawing);
+
+        // Unpadded lines, wide labels and uneven spacing
+        let drawing = "        [Q]\n [A]    [R]   [X]\n 9    10  11    12";
+        let mut stacks = drawing.parse::<Stacks>().unwrap();
+        assert_eq!(stacks.to_string(), drawing);
+        assert_eq!(stacks.tops(), "AQX");
+
+        let procedure = "move 2 from 11 to 10".parse::<Procedure>().unwrap();
+        stacks.apply(&CrateMover9001, &procedure).unwrap();
+        assert_eq!(
+            stacks.to_string(),
+            "     [Q]\n [A] [R]      [X]\n 9    10  11    12"
+        );
+
+        // Non-ASCII labels: columns are chars, not bytes
+        let drawing = "       [X]\n ééé1 ééé2";
+        let stacks = drawing.parse::<Stacks>().unwrap();
+        assert_eq!(stacks.stacks, vec![vec![], vec!['X']]);
+        assert_eq!(stacks.to_string(), drawing);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!("[A]\n 1  2".parse::<Stacks>().is_ok());
+        assert!("[A]\n\n 1".parse::<Stacks>().is_err());
+        assert!("[A]    \n    [B]\n 1   2 ".parse::<Stacks>().is_err());
+        assert!(" [A]\n[B]\n 1 ".parse::<Stacks>().is_err());
+
+        let (mut stacks, _) = parse_input(TEST_INPUT).unwrap();
+        for procedure in ["move 4 from 1 to 2", "move 1 from 4 to 2"] {
+            let procedure = procedure.parse::<Procedure>().unwrap();
+            assert!(stacks.apply(&CrateMover9000, &procedure).is_err());
+        }
+        assert!("move one from 1 to 2".parse::<Procedure>().is_err());
+    }
+}
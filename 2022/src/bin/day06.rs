@@ -1,33 +1,149 @@
-use anyhow::Result;
-use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
 
-fn find_marker(input: &str, marker_size: usize) -> Option<usize> {
-    let mut queue = VecDeque::<u8>::new();
-    for (i, c) in input.as_bytes().iter().enumerate() {
-        if queue.contains(&c) {
-            let mut removed = queue.pop_front();
+use anyhow::{bail, Context, Result};
 
-            while removed != Some(*c) || removed == None {
-                removed = queue.pop_front();
+// How much of the signal is read at a time
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/*
+Instead of searching a window for repeats, remember where each byte value was last seen. The
+current run of distinct bytes starts just after the most recent repeat, so each byte only
+needs one table lookup and the signal never has to be held in memory.
+*/
+struct MarkerDetector {
+    window: usize,
+    // One past the position each byte was last seen at, or 0 if it hasn't been
+    last_seen: [usize; 256],
+    // Where the current run of distinct bytes starts
+    run_start: usize,
+    // Bytes seen so far
+    pos: usize,
+}
+
+impl MarkerDetector {
+    fn new(window: usize) -> Result<Self> {
+        if window == 0 {
+            bail!("marker window must be at least one byte");
+        }
+        Ok(MarkerDetector {
+            window,
+            last_seen: [0; 256],
+            run_start: 0,
+            pos: 0,
+        })
+    }
+
+    /// Feed the next byte, returning whether the last `window` bytes are now all different.
+    fn push(&mut self, byte: u8) -> bool {
+        let seen = &mut self.last_seen[byte as usize];
+        self.run_start = self.run_start.max(*seen);
+        self.pos += 1;
+        *seen = self.pos;
+        self.pos - self.run_start >= self.window
+    }
+}
+
+/// Scan `reader` for markers, calling `on_marker` with the number of bytes read up to and
+/// including each one until it returns false.
+fn scan<R: Read>(
+    mut reader: R,
+    window: usize,
+    mut on_marker: impl FnMut(usize) -> bool,
+) -> Result<()> {
+    let mut detector = MarkerDetector::new(window)?;
+    let mut chunk = vec![0; CHUNK_SIZE];
+
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e).context("could not read the signal"),
+        };
+        for &byte in chunk[..read].iter() {
+            if detector.push(byte) && !on_marker(detector.pos) {
+                return Ok(());
             }
         }
+    }
+}
 
-        queue.push_back(*c);
-        if queue.len() == marker_size {
-            return Some(i + 1);
+// Signal files end in a line break, which isn't part of the signal and would otherwise count as
+// one more distinct byte
+struct WithoutLineBreaks<R>(R);
+
+impl<R: Read> Read for WithoutLineBreaks<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let read = self.0.read(buf)?;
+            let mut kept = 0;
+            for i in 0..read {
+                if !matches!(buf[i], b'\n' | b'\r') {
+                    buf[kept] = buf[i];
+                    kept += 1;
+                }
+            }
+            // Only report the end of the signal when the underlying reader has
+            if kept > 0 || read == 0 {
+                return Ok(kept);
+            }
         }
     }
-    None
+}
+
+fn first_marker<R: Read>(reader: R, window: usize) -> Result<Option<usize>> {
+    let mut first = None;
+    scan(reader, window, |pos| {
+        first = Some(pos);
+        false
+    })?;
+    Ok(first)
+}
+
+fn all_markers<R: Read>(reader: R, window: usize) -> Result<Vec<usize>> {
+    let mut markers = vec![];
+    scan(reader, window, |pos| {
+        markers.push(pos);
+        true
+    })?;
+    Ok(markers)
 }
 
 fn main() -> Result<()> {
-    let input = std::fs::read_to_string("./inputs/day06.txt").unwrap();
+    let path = "./inputs/day06.txt";
+    let signal = || {
+        File::open(path)
+            .map(WithoutLineBreaks)
+            .with_context(|| format!("could not open {}", path))
+    };
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args
+        .iter()
+        .map(|arg| arg.as_str())
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] => {}
+        ["--all", window] => {
+            for pos in all_markers(signal()?, window.parse()?)? {
+                println!("{}", pos);
+            }
+            return Ok(());
+        }
+        [window] => {
+            println!("{:?}", first_marker(signal()?, window.parse()?)?);
+            return Ok(());
+        }
+        _ => bail!("usage: day06 [[--all] <window>]"),
+    }
 
-    let part1 = find_marker(&input, 4);
-    println!("Part 1: {}", part1.unwrap());
+    let part1 = first_marker(signal()?, 4)?;
+    println!("Part 1: {}", part1.context("no start-of-packet marker")?);
 
-    let part2 = find_marker(&input, 14);
-    println!("Part 2: {}", part2.unwrap());
+    let part2 = first_marker(signal()?, 14)?;
+    println!("Part 2: {}", part2.context("no start-of-message marker")?);
 
     Ok(())
 }
@@ -36,24 +152,119 @@ fn main() -> Result<()> {
 mod tests {
     use super::*;
 
+    fn find_marker(input: &str, marker_size: usize) -> Result<Option<usize>> {
+        first_marker(input.as_bytes(), marker_size)
+    }
+
     #[test]
     fn test_one_part1() {
-        assert_eq!(find_marker("bvwbjplbgvbhsrlpgdmjqwftvncz", 4), Some(5));
+        assert_eq!(
+            find_marker("bvwbjplbgvbhsrlpgdmjqwftvncz", 4).unwrap(),
+            Some(5)
+        );
     }
     #[test]
     fn test_two_part1() {
-        assert_eq!(find_marker("nppdvjthqldpwncqszvftbrmjlhg", 4), Some(6));
+        assert_eq!(
+            find_marker("nppdvjthqldpwncqszvftbrmjlhg", 4).unwrap(),
+            Some(6)
+        );
     }
     #[test]
     fn test_three_part1() {
         //                01234567890
         assert_eq!(
-            find_marker("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 4),
+            find_marker("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 4).unwrap(),
             Some(10)
         );
     }
     #[test]
     fn test_four_part1() {
-        assert_eq!(find_marker("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 4), Some(11));
+        assert_eq!(
+            find_marker("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 4).unwrap(),
+            Some(11)
+        );
+    }
+    #[test]
+    fn test_part2() {
+        for (signal, marker) in [
+            ("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 19),
+            ("bvwbjplbgvbhsrlpgdmjqwftvncz", 23),
+            ("nppdvjthqldpwncqszvftbrmjlhg", 23),
+            ("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 29),
+            ("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 26),
+        ] {
+            assert_eq!(find_marker(signal, 14).unwrap(), Some(marker));
+        }
+    }
+
+    #[test]
+    fn test_no_marker() {
+        assert!(matches!(find_marker("abcabc", 4), Ok(None)));
+        assert!(matches!(find_marker("", 1), Ok(None)));
+
+        let error = find_marker("abcd", 0).unwrap_err();
+        assert_eq!(error.to_string(), "marker window must be at least one byte");
+
+        struct Broken;
+        impl Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(ErrorKind::BrokenPipe.into())
+            }
+        }
+        let error = first_marker(Broken, 4).unwrap_err();
+        assert_eq!(error.to_string(), "could not read the signal");
+    }
+
+    #[test]
+    fn test_all_markers() {
+        assert_eq!(all_markers("aabcbcd".as_bytes(), 3).unwrap(), vec![4, 7]);
+        assert_eq!(all_markers("aaa".as_bytes(), 1).unwrap(), vec![1, 2, 3]);
+        assert_eq!(all_markers("abc".as_bytes(), 4).unwrap(), vec![]);
+        assert!(all_markers("abc".as_bytes(), 0).is_err());
+    }
+
+    #[test]
+    fn test_line_breaks() {
+        assert_eq!(all_markers("aab\n".as_bytes(), 3).unwrap(), vec![4]);
+        for signal in ["aab\n", "aab\r\n", "a\nab\n\n"] {
+            let reader = WithoutLineBreaks(signal.as_bytes());
+            assert_eq!(all_markers(reader, 3).unwrap(), vec![], "{:?}", signal);
+        }
+        let reader = WithoutLineBreaks("\nabc\n".as_bytes());
+        assert_eq!(first_marker(reader, 3).unwrap(), Some(3));
+    }
+
+    #[test]
+    fn test_any_bytes_across_chunks() {
+        // A long repetitive signal ending in every byte value once, with the marker straddling
+        // chunk boundaries
+        let mut signal = vec![7u8; CHUNK_SIZE + 100];
+        signal.extend((0..=255u8).rev());
+        let expected = CHUNK_SIZE + 100 + 256;
+        assert_eq!(
+            first_marker(signal.as_slice(), 256).unwrap(),
+            Some(expected)
+        );
+        assert_eq!(
+            first_marker(signal.as_slice(), 257).unwrap(),
+            None,
+            "no window can be longer than the alphabet"
+        );
+
+        // A reader handing over one byte at a time finds the same markers
+        struct Trickle<'a>(&'a [u8]);
+        impl Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let n = self.0.len().min(buf.len()).min(1);
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+        assert_eq!(
+            all_markers(Trickle(&signal), 200).unwrap(),
+            all_markers(signal.as_slice(), 200).unwrap()
+        );
     }
 }
@@ -1,76 +1,416 @@
-use std::cmp;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::str::FromStr;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
+
+const TOTAL_DISK_SPACE: usize = 70_000_000;
+const REQUIRED_SPACE: usize = 30_000_000;
 
 fn main() -> Result<()> {
-    let input = std::fs::read_to_string("./inputs/day07.txt").unwrap();
+    let input = std::fs::read_to_string("./inputs/day07.txt")?;
+    let fs = input.parse::<FileSystem>()?;
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args
+        .iter()
+        .map(|arg| arg.as_str())
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] => {}
+        ["tree"] => print!("{}", fs.tree()),
+        ["du"] => {
+            for (path, size) in fs.du() {
+                println!("{:>10}  {}", size, path);
+            }
+        }
+        ["largest", n] => {
+            for (path, size) in fs.largest_files(n.parse()?) {
+                println!("{:>10}  {}", size, path);
+            }
+        }
+        _ => bail!("usage: day07 [tree | du | largest <n>]"),
+    }
 
-    let (total, smallest) = solution(input);
+    let (total, smallest) = solution(&fs)?;
     println!("Part 1: {}", total);
     println!("Part 2: {}", smallest);
 
     Ok(())
 }
 
-fn solution(input: String) -> (usize, usize) {
-    const TOTAL_DISK_SPACE: usize = 70_000_000;
-    const REQUIRED_SPACE: usize = 30_000_000;
-    let mut all_dirs: Vec<(&str, usize)> = vec![];
+fn solution(fs: &FileSystem) -> Result<(usize, usize)> {
+    let sub_100k_total = fs.sizes().into_iter().filter(|&size| size <= 100_000).sum();
+    let Some((_, smallest)) = fs.smallest_dir_to_free(TOTAL_DISK_SPACE, REQUIRED_SPACE) else {
+        bail!(
+            "no directory frees {} of a {} disk",
+            REQUIRED_SPACE,
+            TOTAL_DISK_SPACE
+        );
+    };
+
+    Ok((sub_100k_total, smallest))
+}
+
+#[derive(Debug, Default)]
+struct Dir {
+    path: String,
+    parent: Option<usize>,
+    dirs: BTreeMap<String, usize>,
+    files: BTreeMap<String, usize>,
+    // Whether an `ls` has been seen, after which the contents are known to be complete
+    listed: bool,
+}
 
-    let mut sub_100k_total: usize = 0;
+/*
+Directories live in an arena with the root at index 0. A directory is always created after its
+parent, so walking the arena backwards visits children before their parents.
+*/
+#[derive(Debug)]
+struct FileSystem {
+    dirs: Vec<Dir>,
+}
 
-    let mut path_stack = input.lines().fold(vec![("/", 0)], |mut path_stack, line| {
-        if line == "$ cd /" || line == "$ ls" {
-            return path_stack;
+impl FileSystem {
+    fn new() -> Self {
+        FileSystem {
+            dirs: vec![Dir {
+                path: "/".to_string(),
+                ..Default::default()
+            }],
         }
+    }
 
-        if line.starts_with("$ cd ") {
-            let dir = &line[5..];
-            if dir == ".." {
-                let (name, size) = path_stack.pop().unwrap();
+    fn child_path(&self, dir: usize, name: &str) -> String {
+        match self.dirs[dir].path.as_str() {
+            "/" => format!("/{}", name),
+            path => format!("{}/{}", path, name),
+        }
+    }
 
-                if size <= 100_000 {
-                    sub_100k_total += size;
+    fn add_dir(&mut self, parent: usize, name: &str) -> usize {
+        let index = self.dirs.len();
+        self.dirs.push(Dir {
+            path: self.child_path(parent, name),
+            parent: Some(parent),
+            ..Default::default()
+        });
+        self.dirs[parent].dirs.insert(name.to_string(), index);
+        index
+    }
+
+    // Record one entry of an `ls` listing of `dir`, checking it against what is already known
+    fn add_entry(&mut self, dir: usize, line: &str) -> Result<()> {
+        let (kind, name) = line
+            .split_once(' ')
+            .ok_or_else(|| anyhow!("malformed listing entry {:?}", line))?;
+        let current = &self.dirs[dir];
+        let path = self.child_path(dir, name);
+
+        if kind == "dir" {
+            if current.files.contains_key(name) {
+                bail!("{} was listed as a file and is now a directory", path);
+            }
+            if !current.dirs.contains_key(name) {
+                if current.listed {
+                    bail!("{} appeared in a later listing of {}", path, current.path);
+                }
+                self.add_dir(dir, name);
+            }
+        } else {
+            let size = kind
+                .parse::<usize>()
+                .with_context(|| format!("invalid size in listing entry {:?}", line))?;
+            if current.dirs.contains_key(name) {
+                bail!("{} was listed as a directory and is now a file", path);
+            }
+            match current.files.get(name) {
+                Some(&old) if old != size => {
+                    bail!("{} was listed with size {} and now {}", path, old, size)
+                }
+                Some(_) => {}
+                None if current.listed => {
+                    bail!("{} appeared in a later listing of {}", path, current.path)
+                }
+                None => {
+                    self.dirs[dir].files.insert(name.to_string(), size);
                 }
-                all_dirs.push((name, size));
-                path_stack.last_mut().unwrap().1 += size;
-            } else {
-                path_stack.push((dir, 0));
             }
-            return path_stack;
         }
+        Ok(())
+    }
 
-        let (amount, _) = line.split_once(" ").unwrap();
+    fn finish_listing(&mut self, dir: usize, entries: usize) -> Result<()> {
+        let current = &mut self.dirs[dir];
+        let known = current.dirs.len() + current.files.len();
+        if current.listed && entries != known {
+            bail!(
+                "listing of {} has {} entries, previously {}",
+                current.path,
+                entries,
+                known
+            );
+        }
+        current.listed = true;
+        Ok(())
+    }
 
-        if let Ok(amount) = amount.parse::<usize>() {
-            path_stack.last_mut().unwrap().1 += amount;
-        } else if amount == "dir" {
-            // no-op
+    /// Total size of every directory, including everything below it, indexed like `dirs`.
+    fn sizes(&self) -> Vec<usize> {
+        let mut sizes = self
+            .dirs
+            .iter()
+            .map(|dir| dir.files.values().sum())
+            .collect::<Vec<usize>>();
+        for (i, dir) in self.dirs.iter().enumerate().rev() {
+            if let Some(parent) = dir.parent {
+                sizes[parent] += sizes[i];
+            }
         }
-        path_stack
-    });
+        sizes
+    }
 
-    while path_stack.len() > 0 {
-        let (name, size) = path_stack.pop().unwrap();
-        if size <= 100_000 {
-            sub_100k_total += size;
+    /// `du`-style (path, total size) for every directory, sorted by path.
+    fn du(&self) -> Vec<(&str, usize)> {
+        let mut du = self
+            .dirs
+            .iter()
+            .zip(self.sizes())
+            .map(|(dir, size)| (dir.path.as_str(), size))
+            .collect::<Vec<_>>();
+        du.sort();
+        du
+    }
+
+    fn largest_files(&self, n: usize) -> Vec<(String, usize)> {
+        let mut files = self
+            .dirs
+            .iter()
+            .enumerate()
+            .flat_map(|(i, dir)| {
+                dir.files
+                    .iter()
+                    .map(move |(name, &size)| (self.child_path(i, name), size))
+            })
+            .collect::<Vec<_>>();
+        files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        files.truncate(n);
+        files
+    }
+
+    /// The smallest directory whose deletion leaves at least `required` bytes free.
+    fn smallest_dir_to_free(&self, total: usize, required: usize) -> Option<(&str, usize)> {
+        let sizes = self.sizes();
+        let unused = total.saturating_sub(sizes[0]);
+        self.dirs
+            .iter()
+            .zip(sizes)
+            .filter(|&(_, size)| size + unused >= required)
+            .min_by_key(|&(_, size)| size)
+            .map(|(dir, size)| (dir.path.as_str(), size))
+    }
+
+    /// Render the tree the same way the puzzle description draws it.
+    fn tree(&self) -> String {
+        let mut out = String::new();
+        self.render(0, "/", 0, &mut out);
+        out
+    }
+
+    fn render(&self, dir: usize, name: &str, depth: usize, out: &mut String) {
+        let indent = "  ".repeat(depth);
+        writeln!(out, "{}- {} (dir)", indent, name).unwrap();
+
+        // Directories and files are listed together in name order
+        let mut entries = self.dirs[dir]
+            .dirs
+            .iter()
+            .map(|(name, &child)| (name, Some(child), 0))
+            .chain(
+                self.dirs[dir]
+                    .files
+                    .iter()
+                    .map(|(name, &size)| (name, None, size)),
+            )
+            .collect::<Vec<_>>();
+        entries.sort();
+
+        for (name, child, size) in entries {
+            match child {
+                Some(child) => self.render(child, name, depth + 1, out),
+                None => writeln!(out, "{}  - {} (file, size={})", indent, name, size).unwrap(),
+            }
+        }
+    }
+}
+
+impl FromStr for FileSystem {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fs = FileSystem::new();
+        let mut cwd = 0;
+        // Number of entries seen so far while an `ls` is being read
+        let mut listing = None;
+
+        for (i, line) in s.lines().enumerate() {
+            let context = || format!("line {}: {:?}", i + 1, line);
+
+            if let Some(command) = line.strip_prefix("$ ") {
+                // A listing is complete once the next command starts
+                if let Some(entries) = listing.take() {
+                    fs.finish_listing(cwd, entries).with_context(context)?;
+                }
+
+                match command.split_once(' ') {
+                    None if command == "ls" => listing = Some(0),
+                    Some(("cd", "/")) => cwd = 0,
+                    Some(("cd", "..")) => {
+                        cwd = fs.dirs[cwd]
+                            .parent
+                            .ok_or_else(|| anyhow!("cd .. from the root"))
+                            .with_context(context)?;
+                    }
+                    Some(("cd", name)) => {
+                        cwd = match fs.dirs[cwd].dirs.get(name) {
+                            Some(&dir) => dir,
+                            None if fs.dirs[cwd].listed => {
+                                return Err(anyhow!(
+                                    "cd into {}, which is not in the listing",
+                                    fs.child_path(cwd, name)
+                                ))
+                                .with_context(context);
+                            }
+                            None => fs.add_dir(cwd, name),
+                        };
+                    }
+                    _ => return Err(anyhow!("unknown command")).with_context(context),
+                }
+            } else if let Some(entries) = listing.as_mut() {
+                fs.add_entry(cwd, line).with_context(context)?;
+                *entries += 1;
+            } else {
+                return Err(anyhow!("output outside of an ls")).with_context(context);
+            }
         }
 
-        if path_stack.len() > 0 {
-            path_stack.last_mut().unwrap().1 += size;
+        if let Some(entries) = listing {
+            fs.finish_listing(cwd, entries)?;
         }
+        Ok(fs)
+    }
+}
+
+#[allow(dead_code)]
+const TEST_INPUT: &str = r#"$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k"#;
 
-        all_dirs.push((name, size));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solution() {
+        let fs = TEST_INPUT.parse::<FileSystem>().unwrap();
+        assert_eq!(solution(&fs).unwrap(), (95437, 24933642));
+        assert_eq!(
+            fs.smallest_dir_to_free(TOTAL_DISK_SPACE, REQUIRED_SPACE),
+            Some(("/d", 24933642))
+        );
+        // More than the whole disk can never be freed
+        assert_eq!(
+            fs.smallest_dir_to_free(TOTAL_DISK_SPACE, TOTAL_DISK_SPACE + 1),
+            None
+        );
     }
 
-    let unused_space = TOTAL_DISK_SPACE - all_dirs.last().unwrap().1;
+    #[test]
+    fn test_queries() {
+        let fs = TEST_INPUT.parse::<FileSystem>().unwrap();
+        assert_eq!(
+            fs.du(),
+            vec![
+                ("/", 48381165),
+                ("/a", 94853),
+                ("/a/e", 584),
+                ("/d", 24933642)
+            ]
+        );
+        assert_eq!(
+            fs.largest_files(2),
+            vec![
+                ("/b.txt".to_string(), 14848514),
+                ("/c.dat".to_string(), 8504156)
+            ]
+        );
+        assert_eq!(
+            fs.tree(),
+            r#"- / (dir)
+  - a (dir)
+    - e (dir)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+"#
+        );
+    }
 
-    let mut smallest = usize::MAX;
-    for (_name, size) in all_dirs {
-        if size + unused_space >= REQUIRED_SPACE {
-            smallest = cmp::min(smallest, size);
-        }
+    #[test]
+    fn test_same_name_dirs() {
+        let fs = "$ cd /\n$ ls\ndir a\ndir b\n$ cd a\n$ ls\ndir a\n$ cd a\n$ ls\n10 x\n$ cd /\n$ cd b\n$ ls\ndir a\n$ cd a\n$ ls\n20 x"
+            .parse::<FileSystem>()
+            .unwrap();
+        let du = fs.du();
+        assert!(du.contains(&("/a/a", 10)));
+        assert!(du.contains(&("/b/a", 20)));
     }
 
-    (sub_100k_total, smallest)
+    #[test]
+    fn test_inconsistent_listings() {
+        for transcript in [
+            "$ cd /\n$ ls\n10 x\n$ ls\n20 x",
+            "$ cd /\n$ ls\n10 x\n$ ls\n10 x\n5 y",
+            "$ cd /\n$ ls\n10 x\n5 y\n$ ls\n10 x",
+            "$ cd /\n$ ls\ndir x\n$ ls\n10 x",
+            "$ cd /\n$ ls\n$ cd missing",
+            "$ cd ..",
+            "10 x",
+        ] {
+            assert!(
+                transcript.parse::<FileSystem>().is_err(),
+                "{:?}",
+                transcript
+            );
+        }
+    }
 }
@@ -1,110 +1,200 @@
-use anyhow::Result;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+
+// Shades for the heatmap, from the lowest scenic score to the highest
+const HEATMAP_SHADES: &[u8] = b" .:-=+*#%@";
 
 fn main() -> Result<()> {
-    let input = std::fs::read_to_string("./inputs/day08.txt").unwrap();
-    let trees = input.lines().fold(vec![], |mut trees, line| {
-        trees.push(
-            line.chars()
-                .map(|c| c.to_digit(10).unwrap())
-                .collect::<Vec<_>>(),
-        );
-        trees
-    });
-
-    let width = trees[0].len();
-    let height = trees.len();
-
-    let mut visible_trees = 0;
-    let mut max_scenic_score = 0;
-
-    for row in 0..height {
-        for col in 0..width {
-            if row == 0 || row == height - 1 || col == 0 || col == width - 1 {
-                visible_trees += 1;
-                continue;
-            }
+    let (mut show_mask, mut show_heatmap) = (false, false);
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--mask" => show_mask = true,
+            "--heatmap" => show_heatmap = true,
+            _ => bail!("usage: day08 [--mask] [--heatmap]"),
+        }
+    }
 
-            let (north_visible, north_trees) = check_north(&trees, row, col);
+    let input = std::fs::read_to_string("./inputs/day08.txt")?;
+    let forest = input.parse::<Forest>()?;
+    let view = forest.view();
 
-            let (south_visible, south_trees) = check_south(&trees, row, col);
+    println!("Part 1: {}", view.visible_count());
+    println!("Part 2: {}", view.max_score());
 
-            let (east_visible, east_trees) = check_east(&trees, row, col);
+    if show_mask {
+        print!("{}", view.mask());
+    }
+    if show_heatmap {
+        print!("{}", view.heatmap());
+    }
 
-            let (west_visible, west_trees) = check_west(&trees, row, col);
+    Ok(())
+}
 
-            let scenic_score = north_trees * south_trees * east_trees * west_trees;
-            max_scenic_score = std::cmp::max(max_scenic_score, scenic_score);
+#[derive(Debug)]
+struct Forest {
+    heights: Vec<Vec<u8>>,
+}
 
-            if north_visible || south_visible || east_visible || west_visible {
-                visible_trees += 1;
+impl FromStr for Forest {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let heights = s
+            .lines()
+            .enumerate()
+            .map(|(row, line)| {
+                line.chars()
+                    .enumerate()
+                    .map(|(col, c)| {
+                        c.to_digit(10).map(|h| h as u8).ok_or_else(|| {
+                            anyhow!("invalid height {:?} at row {}, col {}", c, row + 1, col + 1)
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        match heights.first() {
+            None => bail!("the forest has no trees"),
+            Some(first) if heights.iter().any(|row| row.len() != first.len()) => {
+                bail!("rows of the forest are not all the same length")
             }
+            _ => Ok(Forest { heights }),
         }
     }
-    println!("Part 1: {}", visible_trees);
-    println!("Part 2: {}", max_scenic_score);
-
-    Ok(())
 }
 
-fn check_north(trees: &Vec<Vec<u32>>, row: usize, col: usize) -> (bool, usize) {
-    let mut north = row.checked_sub(1).unwrap();
+// What every tree sees, indexed like the forest
+#[derive(Debug)]
+struct View {
+    visible: Vec<Vec<bool>>,
+    scores: Vec<Vec<usize>>,
+}
 
-    let mut visible_trees = 0;
-    loop {
-        visible_trees += 1;
+impl Forest {
+    /*
+    Each row and column is swept once in each direction while keeping a stack of the trees that
+    nothing since has been at least as tall as. Popping everything shorter than the current tree
+    leaves the tree that blocks its view on top, or nothing when it can be seen from the edge.
+    Every tree is pushed and popped at most once per sweep, so the whole forest takes O(n^2).
+    */
+    fn view(&self) -> View {
+        let (rows, cols) = (self.heights.len(), self.heights[0].len());
+        let mut visible = vec![vec![false; cols]; rows];
+        let mut scores = vec![vec![1; cols]; rows];
+
+        // Lines of sight, in the order a viewer looking back towards the start would see them
+        let mut lines = vec![];
+        for r in 0..rows {
+            let line = (0..cols).map(|c| (r, c)).collect::<Vec<_>>();
+            lines.push(line.iter().rev().copied().collect());
+            lines.push(line);
+        }
+        for c in 0..cols {
+            let line = (0..rows).map(|r| (r, c)).collect::<Vec<_>>();
+            lines.push(line.iter().rev().copied().collect());
+            lines.push(line);
+        }
 
-        if trees[north][col] >= trees[row][col] {
-            return (false, visible_trees);
+        let mut stack: Vec<usize> = vec![];
+        for line in lines {
+            stack.clear();
+            for (i, &(r, c)) in line.iter().enumerate() {
+                let height = self.heights[r][c];
+                while let Some(&top) = stack.last() {
+                    let (tr, tc) = line[top];
+                    if self.heights[tr][tc] >= height {
+                        break;
+                    }
+                    stack.pop();
+                }
+
+                match stack.last() {
+                    Some(&blocker) => scores[r][c] *= i - blocker,
+                    None => {
+                        visible[r][c] = true;
+                        scores[r][c] *= i;
+                    }
+                }
+                stack.push(i);
+            }
         }
-        north = match north.checked_sub(1) {
-            Some(north) => north,
-            None => break,
-        };
+
+        View { visible, scores }
     }
-    (true, visible_trees)
 }
 
-fn check_south(trees: &Vec<Vec<u32>>, row: usize, col: usize) -> (bool, usize) {
-    let mut visible_trees = 0;
-    let mut south = row + 1;
-    while south <= trees.len() - 1 {
-        visible_trees += 1;
+impl View {
+    fn visible_count(&self) -> usize {
+        self.visible.iter().flatten().filter(|&&v| v).count()
+    }
 
-        if trees[south][col] >= trees[row][col] {
-            return (false, visible_trees);
-        }
-        south += 1;
+    fn max_score(&self) -> usize {
+        self.scores.iter().flatten().copied().max().unwrap_or(0)
     }
-    (true, visible_trees)
-}
 
-fn check_east(trees: &Vec<Vec<u32>>, row: usize, col: usize) -> (bool, usize) {
-    let mut visible_trees = 0;
-    let mut east = col + 1;
-    while east <= trees[0].len() - 1 {
-        visible_trees += 1;
+    /// Visible trees as '#' and hidden ones as '.'.
+    fn mask(&self) -> String {
+        self.visible
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&v| if v { '#' } else { '.' })
+                    .collect::<String>()
+                    + "\n"
+            })
+            .collect()
+    }
 
-        if trees[row][east] >= trees[row][col] {
-            return (false, visible_trees);
-        }
-        east += 1;
+    /// Scenic scores shaded relative to the highest one.
+    fn heatmap(&self) -> String {
+        let max = self.max_score().max(1);
+        let top = HEATMAP_SHADES.len() - 1;
+        self.scores
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&score| HEATMAP_SHADES[score * top / max] as char)
+                    .collect::<String>()
+                    + "\n"
+            })
+            .collect()
     }
-    (true, visible_trees)
 }
 
-fn check_west(trees: &Vec<Vec<u32>>, row: usize, col: usize) -> (bool, usize) {
-    let mut visible_trees = 0;
-    let mut west = col.checked_sub(1).unwrap();
-    loop {
-        visible_trees += 1;
+#[allow(dead_code)]
+const TEST_INPUT: &str = r#"30373
+25512
+65332
+33549
+35390"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parts() {
+        let view = TEST_INPUT.parse::<Forest>().unwrap().view();
+        assert_eq!(view.visible_count(), 21);
+        assert_eq!(view.max_score(), 8);
+        assert_eq!(view.scores[1][2], 4);
+        assert_eq!(view.scores[3][2], 8);
+    }
+
+    #[test]
+    fn test_renders() {
+        let view = TEST_INPUT.parse::<Forest>().unwrap().view();
+        assert_eq!(view.mask(), "#####\n###.#\n##.##\n#.#.#\n#####\n");
+        assert_eq!(view.heatmap().lines().nth(3), Some(" .@- "));
+    }
 
-        if trees[row][west] >= trees[row][col] {
-            return (false, visible_trees);
-        }
-        west = match west.checked_sub(1) {
-            Some(west) => west,
-            None => break,
-        };
+    #[test]
+    fn test_invalid() {
+        assert!("".parse::<Forest>().is_err());
+        assert!("12\n3".parse::<Forest>().is_err());
+        assert!("1a".parse::<Forest>().is_err());
     }
-    (true, visible_trees)
 }
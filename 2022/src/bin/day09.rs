@@ -1,6 +1,6 @@
 use std::{collections::HashSet, str::FromStr};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 
 #[allow(dead_code)]
 const TEST_INPUT: &str = r#"R 4
@@ -22,27 +22,12 @@ D 10
 L 25
 U 20"#;
 
-#[derive(Debug, Clone)]
-pub struct Knot {
-    pub x: i32,
-    pub y: i32,
-    pub history: HashSet<(i32, i32)>,
-}
+type Pos = (i32, i32);
 
-impl Knot {
-    fn new() -> Self {
-        let mut history = HashSet::new();
-        history.insert((0, 0));
-        Knot {
-            x: 0,
-            y: 0,
-            history: history,
-        }
-    }
-}
 #[derive(Debug, PartialEq)]
 struct Motion {
-    dir: char,
+    // One step of the head, with y pointing up
+    dir: Pos,
     distance: usize,
 }
 
@@ -50,140 +35,177 @@ impl FromStr for Motion {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (dir, distance) = s.split_once(" ").unwrap();
-        let dir = dir.chars().next().unwrap();
-        let distance = distance.parse::<usize>()?;
+        let (dir, distance) = s
+            .split_once(' ')
+            .ok_or_else(|| anyhow!("missing distance in {:?}", s))?;
+        let dir = match dir {
+            "R" => (1, 0),
+            "L" => (-1, 0),
+            "U" => (0, 1),
+            "D" => (0, -1),
+            "UR" => (1, 1),
+            "UL" => (-1, 1),
+            "DR" => (1, -1),
+            "DL" => (-1, -1),
+            _ => bail!("unknown direction {:?}", dir),
+        };
+        let distance = distance
+            .parse::<usize>()
+            .with_context(|| format!("invalid distance in {:?}", s))?;
         Ok(Motion { dir, distance })
     }
 }
 
-fn main() -> Result<()> {
-    let input = std::fs::read_to_string("./inputs/day09.txt").unwrap();
-    let movements = input
-        .lines()
-        .map(|line| line.parse::<Motion>().unwrap())
-        .collect::<Vec<_>>();
-
-    let parts = solution(&movements);
-    println!("Part 1: {:?}", parts.0);
-    println!("Part 2: {:?}", parts.1);
-    Ok(())
+/*
+Every knot follows the one in front of it by stepping one square towards it along each axis, as
+soon as they stop touching. Knot 1 of a long rope moves exactly like the tail of a two knot
+rope, since nothing behind a knot affects it.
+*/
+#[derive(Debug)]
+struct Rope {
+    knots: Vec<Pos>,
+    // Squares visited by each knot we were asked to track
+    visited: Vec<(usize, HashSet<Pos>)>,
 }
 
-fn solution(movements: &Vec<Motion>) -> (usize, usize) {
-    let part1_knots: Vec<Knot> = vec![Knot::new(), Knot::new()];
+impl Rope {
+    fn new(len: usize, tracked: &[usize]) -> Result<Self> {
+        if len == 0 {
+            bail!("a rope needs at least one knot");
+        }
+        if let Some(knot) = tracked.iter().find(|&&knot| knot >= len) {
+            bail!("can't track knot {} of a {} knot rope", knot, len);
+        }
 
-    let part2_knots: Vec<Knot> = vec![
-        Knot::new(),
-        Knot::new(),
-        Knot::new(),
-        Knot::new(),
-        Knot::new(),
-        Knot::new(),
-        Knot::new(),
-        Knot::new(),
-        Knot::new(),
-        Knot::new(),
-    ];
+        Ok(Rope {
+            knots: vec![(0, 0); len],
+            visited: tracked
+                .iter()
+                .map(|&knot| (knot, HashSet::from([(0, 0)])))
+                .collect(),
+        })
+    }
 
-    let part1 = move_knots(&movements, part1_knots);
+    fn step(&mut self, (dx, dy): Pos) {
+        self.knots[0].0 += dx;
+        self.knots[0].1 += dy;
 
-    let part2 = move_knots(&movements, part2_knots);
+        for i in 1..self.knots.len() {
+            let (head, tail) = (self.knots[i - 1], self.knots[i]);
+            let (gap_x, gap_y) = (head.0 - tail.0, head.1 - tail.1);
+            if gap_x.abs() <= 1 && gap_y.abs() <= 1 {
+                // Everything further back stays put too
+                break;
+            }
+            self.knots[i] = (tail.0 + gap_x.signum(), tail.1 + gap_y.signum());
+        }
 
-    (part1, part2)
-}
+        for (knot, visited) in self.visited.iter_mut() {
+            visited.insert(self.knots[*knot]);
+        }
+    }
 
-fn move_knots(movements: &Vec<Motion>, mut knots: Vec<Knot>) -> usize {
-    for motion in movements {
-        match motion.dir {
-            'R' => {
-                for _ in 0..motion.distance {
-                    knots[0].x += 1; // head
-                    for i in 0..knots.len() - 1 {
-                        move_tail(i, i + 1, &mut knots);
-                    }
-                }
-            }
-            'L' => {
-                for _ in 0..motion.distance {
-                    knots[0].x -= 1; // head
-                    for i in 0..knots.len() - 1 {
-                        move_tail(i, i + 1, &mut knots);
-                    }
-                }
-            }
-            'U' => {
-                for _ in 0..motion.distance {
-                    knots[0].y += 1; // head
-                    for i in 0..knots.len() - 1 {
-                        move_tail(i, i + 1, &mut knots);
-                    }
-                }
+    /// Apply every motion, calling `on_step` with all knot positions after each single step.
+    fn run(&mut self, motions: &[Motion], mut on_step: impl FnMut(&[Pos])) {
+        for motion in motions {
+            for _ in 0..motion.distance {
+                self.step(motion.dir);
+                on_step(&self.knots);
             }
-            'D' => {
-                for _ in 0..motion.distance {
-                    knots[0].y -= 1; // head
-                    for i in 0..knots.len() - 1 {
-                        move_tail(i, i + 1, &mut knots);
-                    }
-                }
-            }
-            _ => panic!("Unknown direction"),
         }
     }
-    knots.last().unwrap().history.len()
+
+    fn visited(&self, knot: usize) -> Option<usize> {
+        self.visited
+            .iter()
+            .find(|(tracked, _)| *tracked == knot)
+            .map(|(_, visited)| visited.len())
+    }
 }
 
-fn move_tail(head: usize, tail: usize, knots: &mut Vec<Knot>) {
-    if (knots[head].x - knots[tail].x).abs() > 1 {
-        if knots[head].x > knots[tail].x {
-            knots[tail].x += 1;
+fn main() -> Result<()> {
+    let mut knots = None;
+    let mut animate = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--knots" => knots = Some(args.next().context("--knots needs a count")?.parse()?),
+            "--animate" => animate = true,
+            _ => bail!("usage: day09 [--knots <n>] [--animate]"),
+        }
+    }
 
-            if knots[head].y > knots[tail].y {
-                knots[tail].y += 1;
-            } else if knots[head].y < knots[tail].y {
-                knots[tail].y -= 1;
-            }
+    let input = std::fs::read_to_string("./inputs/day09.txt")?;
+    let movements = input
+        .lines()
+        .map(|line| line.parse::<Motion>())
+        .collect::<Result<Vec<_>>>()?;
+
+    if knots.is_none() && !animate {
+        let parts = solution(&movements)?;
+        println!("Part 1: {:?}", parts.0);
+        println!("Part 2: {:?}", parts.1);
+        return Ok(());
+    }
 
-            let (x, y) = (knots[tail].x, knots[tail].y);
-            knots[tail].history.insert((x, y));
-        } else if knots[head].x < knots[tail].x {
-            knots[tail].x -= 1;
+    // One line per step with every knot's position, head first
+    let len = knots.unwrap_or(10);
+    let mut rope = Rope::new(len, &[len.saturating_sub(1)])?;
+    rope.run(&movements, |knots| {
+        if animate {
+            let frame = knots
+                .iter()
+                .map(|(x, y)| format!("{},{}", x, y))
+                .collect::<Vec<_>>();
+            println!("{}", frame.join(" "));
+        }
+    });
+    println!("Tail of {} knots: {}", len, rope.visited(len - 1).unwrap());
+    Ok(())
+}
 
-            if knots[head].y > knots[tail].y {
-                knots[tail].y += 1;
-            } else if knots[head].y < knots[tail].y {
-                knots[tail].y -= 1;
-            }
+fn solution(movements: &[Motion]) -> Result<(usize, usize)> {
+    let mut rope = Rope::new(10, &[1, 9])?;
+    rope.run(movements, |_| {});
 
-            let (x, y) = (knots[tail].x, knots[tail].y);
-            knots[tail].history.insert((x, y));
-        }
-    }
+    Ok((rope.visited(1).unwrap(), rope.visited(9).unwrap()))
+}
 
-    if (knots[head].y - knots[tail].y).abs() > 1 {
-        if knots[head].y > knots[tail].y {
-            knots[tail].y += 1;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            if knots[head].x > knots[tail].x {
-                knots[tail].x += 1;
-            } else if knots[head].x < knots[tail].x {
-                knots[tail].x -= 1;
-            }
+    fn motions(input: &str) -> Vec<Motion> {
+        input
+            .lines()
+            .map(|line| line.parse::<Motion>().unwrap())
+            .collect()
+    }
 
-            let (x, y) = (knots[tail].x, knots[tail].y);
-            knots[tail].history.insert((x, y));
-        } else if knots[head].y < knots[tail].y {
-            knots[tail].y -= 1;
+    #[test]
+    fn test_solution() {
+        assert_eq!(solution(&motions(TEST_INPUT)).unwrap(), (13, 1));
+        assert_eq!(solution(&motions(TEST_INPUT2)).unwrap().1, 36);
+    }
 
-            if knots[head].x > knots[tail].x {
-                knots[tail].x += 1;
-            } else if knots[head].x < knots[tail].x {
-                knots[tail].x -= 1;
-            }
+    #[test]
+    fn test_diagonal_motions() {
+        // A diagonal head drags the whole rope out in a straight diagonal line
+        let mut rope = Rope::new(3, &[2]).unwrap();
+        let mut frames = vec![];
+        rope.run(&motions("UR 4\nDL 1"), |knots| frames.push(knots.to_vec()));
+        assert_eq!(frames.len(), 5);
+        assert_eq!(frames[3], vec![(4, 4), (3, 3), (2, 2)]);
+        assert_eq!(frames[4], vec![(3, 3), (3, 3), (2, 2)]);
+        assert_eq!(rope.visited(2), Some(3));
+        assert_eq!(rope.visited(1), None);
+    }
 
-            let (x, y) = (knots[tail].x, knots[tail].y);
-            knots[tail].history.insert((x, y));
-        }
+    #[test]
+    fn test_invalid() {
+        assert!("X 4".parse::<Motion>().is_err());
+        assert!("R".parse::<Motion>().is_err());
+        assert!(Rope::new(0, &[]).is_err());
+        assert!(Rope::new(2, &[2]).is_err());
     }
 }
@@ -1,54 +1,403 @@
-use anyhow::Result;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+const CRT_WIDTH: usize = 40;
+const CRT_HEIGHT: usize = 6;
+const SIGNAL_CYCLES: [usize; 6] = [20, 60, 100, 140, 180, 220];
 
 fn main() -> Result<()> {
-    let input = std::fs::read_to_string("./inputs/day10.txt").unwrap();
+    let mut trace = false;
+    let mut breakpoints = BTreeSet::new();
+    let mut draw = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--trace" => trace = true,
+            "--break" => {
+                for cycle in args.next().unwrap_or_default().split(',') {
+                    breakpoints.insert(cycle.parse::<usize>()?);
+                }
+            }
+            "--draw" => draw = args.next(),
+            _ => bail!("usage: day10 [--trace] [--break <cycle>,<cycle>...] [--draw <TEXT>]"),
+        }
+    }
+
+    if let Some(text) = draw {
+        for instruction in assemble_text(&text)? {
+            println!("{}", instruction);
+        }
+        return Ok(());
+    }
 
-    solution(&input);
+    let input = std::fs::read_to_string("./inputs/day10.txt")?;
+    let program = parse_program(&input)?;
+
+    let mut cpu = Cpu::new(program);
+    cpu.breakpoints = breakpoints;
+    let mut crt = Crt::new();
+    let mut sampler = SignalSampler::new(&SIGNAL_CYCLES);
+    let mut tracer = Tracer;
+
+    loop {
+        let mut observers: Vec<&mut dyn Observer> = vec![&mut crt, &mut sampler];
+        if trace {
+            observers.push(&mut tracer);
+        }
+        match cpu.run(&mut observers) {
+            Stop::Halted => break,
+            Stop::Breakpoint => println!("break: {}", cpu),
+        }
+    }
+
+    println!("Part 1: {}", sampler.sum);
+    println!("Part 2:");
+    print!("{}", crt);
     Ok(())
 }
 
-fn handle_tick(tick: usize, register: i32, crt: &mut [[char; 40]; 6], signal_sum: &mut i32) {
-    if (register - (tick % 40) as i32).abs() <= 1 {
-        crt[tick / 40][tick % 40] = '#';
-    } else {
-        crt[tick / 40][tick % 40] = ' ';
+/*
+To add an instruction, add a variant here and teach `from_str`, `cycles` and `execute` about it.
+The CPU itself only knows that an instruction takes some number of cycles and then updates the
+registers.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Instruction {
+    Noop,
+    Addx(i32),
+}
+
+impl Instruction {
+    fn cycles(&self) -> usize {
+        match self {
+            Instruction::Noop => 1,
+            Instruction::Addx(_) => 2,
+        }
     }
-    // ticks start at 0 so the 20th tick is actually tick 19
-    if [20, 60, 100, 140, 180, 220].contains(&(tick + 1)) {
-        *signal_sum += register * (tick as i32 + 1);
+
+    // Applied after the instruction's last cycle has finished
+    fn execute(&self, x: &mut i32) {
+        match self {
+            Instruction::Noop => {}
+            Instruction::Addx(v) => *x += v,
+        }
     }
 }
 
-fn solution(input: &str) {
-    let mut tick: usize = 0;
-    let mut register: i32 = 1;
-    let mut signal_sum = 0;
-    let mut crt: [[char; 40]; 6] = [[' '; 40]; 6];
+impl FromStr for Instruction {
+    type Err = anyhow::Error;
 
-    input.lines().for_each(|line| {
-        if line == "noop" {
-            handle_tick(tick, register, &mut crt, &mut signal_sum);
-            tick += 1;
-        } else if line.starts_with("addx") {
-            let x = &line[5..].parse::<i32>().unwrap();
-            handle_tick(tick, register, &mut crt, &mut signal_sum);
-            tick += 1;
-            handle_tick(tick, register, &mut crt, &mut signal_sum);
-            register += x;
-            tick += 1;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let instruction = match (parts.next(), parts.next()) {
+            (Some("noop"), None) => Instruction::Noop,
+            (Some("addx"), Some(v)) => Instruction::Addx(v.parse()?),
+            _ => bail!("unknown instruction {:?}", s),
+        };
+        if parts.next().is_some() {
+            bail!("too many operands in {:?}", s);
         }
-    });
+        Ok(instruction)
+    }
+}
 
-    println!("Part 1: {}", signal_sum);
-    println!("Part 2:");
-    for row in crt.iter() {
-        for col in row.iter() {
-            print!("{}", col);
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::Noop => write!(f, "noop"),
+            Instruction::Addx(v) => write!(f, "addx {}", v),
+        }
+    }
+}
+
+fn parse_program(input: &str) -> Result<Vec<Instruction>> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            line.parse::<Instruction>()
+                .with_context(|| anyhow!("line {}", i + 1))
+        })
+        .collect()
+}
+
+// Called once per cycle, while the cycle is in progress and before any instruction finishing
+// on it has updated the registers
+trait Observer {
+    fn on_cycle(&mut self, cpu: &Cpu);
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Stop {
+    Halted,
+    Breakpoint,
+}
+
+#[derive(Debug)]
+struct Cpu {
+    program: Vec<Instruction>,
+    pc: usize,
+    // Cycles already spent on the instruction at pc
+    elapsed: usize,
+    // The cycle in progress, counting from 1 like the puzzle does
+    cycle: usize,
+    x: i32,
+    breakpoints: BTreeSet<usize>,
+}
+
+impl Cpu {
+    fn new(program: Vec<Instruction>) -> Self {
+        Cpu {
+            program,
+            pc: 0,
+            elapsed: 0,
+            cycle: 1,
+            x: 1,
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    fn current(&self) -> Option<Instruction> {
+        self.program.get(self.pc).copied()
+    }
+
+    /// Run a single cycle, returning false if the program has already finished.
+    fn step(&mut self, observers: &mut [&mut dyn Observer]) -> bool {
+        let Some(instruction) = self.current() else {
+            return false;
+        };
+
+        for observer in observers.iter_mut() {
+            observer.on_cycle(self);
+        }
+
+        self.elapsed += 1;
+        if self.elapsed == instruction.cycles() {
+            instruction.execute(&mut self.x);
+            self.pc += 1;
+            self.elapsed = 0;
+        }
+        self.cycle += 1;
+        true
+    }
+
+    /// Run until the program ends or a breakpoint cycle is about to start.
+    fn run(&mut self, observers: &mut [&mut dyn Observer]) -> Stop {
+        // Always make progress, so running again after a breakpoint continues past it
+        if !self.step(observers) {
+            return Stop::Halted;
+        }
+        loop {
+            if self.breakpoints.contains(&self.cycle) && self.current().is_some() {
+                return Stop::Breakpoint;
+            }
+            if !self.step(observers) {
+                return Stop::Halted;
+            }
+        }
+    }
+}
+
+impl fmt::Display for Cpu {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cycle {:>3}  X={:>3}", self.cycle, self.x)?;
+        if let Some(instruction) = self.current() {
+            write!(
+                f,
+                "  {} ({}/{})",
+                instruction,
+                self.elapsed + 1,
+                instruction.cycles()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+struct Crt {
+    pixels: [[char; CRT_WIDTH]; CRT_HEIGHT],
+}
+
+impl Crt {
+    fn new() -> Self {
+        Crt {
+            pixels: [[' '; CRT_WIDTH]; CRT_HEIGHT],
+        }
+    }
+}
+
+// The pixel being drawn is lit if the 3-wide sprite centred on X covers it
+fn sprite_covers(x: i32, col: usize) -> bool {
+    (x - col as i32).abs() <= 1
+}
+
+impl Observer for Crt {
+    fn on_cycle(&mut self, cpu: &Cpu) {
+        let pixel = cpu.cycle - 1;
+        if pixel >= CRT_WIDTH * CRT_HEIGHT {
+            return;
+        }
+        let (row, col) = (pixel / CRT_WIDTH, pixel % CRT_WIDTH);
+        self.pixels[row][col] = if sprite_covers(cpu.x, col) { '#' } else { ' ' };
+    }
+}
+
+impl fmt::Display for Crt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.pixels.iter() {
+            writeln!(f, "{}", row.iter().collect::<String>())?;
+        }
+        Ok(())
+    }
+}
+
+struct SignalSampler {
+    cycles: Vec<usize>,
+    sum: i32,
+}
+
+impl SignalSampler {
+    fn new(cycles: &[usize]) -> Self {
+        SignalSampler {
+            cycles: cycles.to_vec(),
+            sum: 0,
+        }
+    }
+}
+
+impl Observer for SignalSampler {
+    fn on_cycle(&mut self, cpu: &Cpu) {
+        if self.cycles.contains(&cpu.cycle) {
+            self.sum += cpu.x * cpu.cycle as i32;
         }
-        println!();
     }
 }
 
+struct Tracer;
+
+impl Observer for Tracer {
+    fn on_cycle(&mut self, cpu: &Cpu) {
+        println!("{}", cpu);
+    }
+}
+
+// The 4x6 capital letters the CRT draws, each followed by a blank column on screen
+const GLYPH_WIDTH: usize = 4;
+const GLYPHS: [(char, [&str; CRT_HEIGHT]); 17] = [
+    (' ', ["....", "....", "....", "....", "....", "...."]),
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+fn render_text(text: &str) -> Result<[[char; CRT_WIDTH]; CRT_HEIGHT]> {
+    let max_letters = CRT_WIDTH / (GLYPH_WIDTH + 1);
+    if text.chars().count() > max_letters {
+        bail!("at most {} letters fit on the CRT", max_letters);
+    }
+
+    let mut pixels = [[' '; CRT_WIDTH]; CRT_HEIGHT];
+    for (i, c) in text.chars().enumerate() {
+        let (_, glyph) = GLYPHS
+            .iter()
+            .find(|(letter, _)| *letter == c)
+            .ok_or_else(|| anyhow!("no glyph for {:?}", c))?;
+        for (row, line) in glyph.iter().enumerate() {
+            for (j, pixel) in line.chars().enumerate() {
+                if pixel == '#' {
+                    pixels[row][i * (GLYPH_WIDTH + 1) + j] = '#';
+                }
+            }
+        }
+    }
+    Ok(pixels)
+}
+
+/*
+Finds a program whose X register puts the sprite over exactly the lit pixels of `target`. Working
+backwards from the last pixel, feasible[p][x] says whether pixels p.. can be drawn correctly by
+instructions starting at pixel p with X = x. The program is then read off forwards from X = 1,
+using a noop whenever that works and an addx to a feasible value otherwise.
+*/
+fn assemble(target: &[[char; CRT_WIDTH]; CRT_HEIGHT]) -> Result<Vec<Instruction>> {
+    // Any X outside this range is as dark as its end points, so there is no need to go further
+    const X_MIN: i32 = -2;
+    const X_MAX: i32 = CRT_WIDTH as i32 + 1;
+    let xs = || X_MIN..=X_MAX;
+    let slot = |x: i32| (x - X_MIN) as usize;
+
+    let pixels = CRT_WIDTH * CRT_HEIGHT;
+    let correct = |p: usize, x: i32| {
+        let (row, col) = (p / CRT_WIDTH, p % CRT_WIDTH);
+        sprite_covers(x, col) == (target[row][col] == '#')
+    };
+
+    let mut feasible = vec![vec![false; slot(X_MAX) + 1]; pixels + 1];
+    feasible[pixels].fill(true);
+    for p in (0..pixels).rev() {
+        let any_after_addx = p + 2 <= pixels && feasible[p + 2].iter().any(|&ok| ok);
+        for x in xs() {
+            feasible[p][slot(x)] = correct(p, x)
+                && (feasible[p + 1][slot(x)] || (any_after_addx && correct(p + 1, x)));
+        }
+    }
+
+    let mut x = 1;
+    if !feasible[0][slot(x)] {
+        // X can't change before the third cycle, when the sprite still covers pixels 0..=2
+        if target[0][0] != '#' || target[0][1] != '#' {
+            bail!("the CRT always lights the first two pixels of the top row");
+        }
+        bail!("the CRT cannot draw this image");
+    }
+
+    let mut program = vec![];
+    let mut p = 0;
+    while p < pixels {
+        if feasible[p + 1][slot(x)] {
+            program.push(Instruction::Noop);
+            p += 1;
+        } else {
+            let next = xs().find(|&next| feasible[p + 2][slot(next)]).unwrap();
+            program.push(Instruction::Addx(next - x));
+            x = next;
+            p += 2;
+        }
+    }
+    Ok(program)
+}
+
+/// Assemble a program drawing `text` and check it by running it through the emulator.
+fn assemble_text(text: &str) -> Result<Vec<Instruction>> {
+    let target = render_text(text)?;
+    let program = assemble(&target)?;
+
+    let mut cpu = Cpu::new(program.clone());
+    let mut crt = Crt::new();
+    while cpu.run(&mut [&mut crt]) != Stop::Halted {}
+    if crt.pixels != target {
+        bail!("assembled program draws\n{}", crt);
+    }
+    Ok(program)
+}
+
 #[allow(dead_code)]
 const TEST_INPUT: &str = r#"addx 15
 addx -11
@@ -196,3 +545,65 @@ addx -11
 noop
 noop
 noop"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(program: Vec<Instruction>) -> (Crt, SignalSampler) {
+        let mut cpu = Cpu::new(program);
+        let mut crt = Crt::new();
+        let mut sampler = SignalSampler::new(&SIGNAL_CYCLES);
+        while cpu.run(&mut [&mut crt, &mut sampler]) != Stop::Halted {}
+        (crt, sampler)
+    }
+
+    #[test]
+    fn test_solution() {
+        let (crt, sampler) = run(parse_program(TEST_INPUT).unwrap());
+        assert_eq!(sampler.sum, 13140);
+        assert_eq!(
+            crt.to_string(),
+            "##  ##  ##  ##  ##  ##  ##  ##  ##  ##  
+###   ###   ###   ###   ###   ###   ### 
+####    ####    ####    ####    ####    
+#####     #####     #####     #####     
+######      ######      ######      ####
+#######       #######       #######     
+"
+        );
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut cpu = Cpu::new(parse_program("noop\naddx 3\naddx -5").unwrap());
+        cpu.breakpoints = BTreeSet::from([3, 5]);
+
+        assert_eq!(cpu.run(&mut []), Stop::Breakpoint);
+        assert_eq!((cpu.cycle, cpu.x), (3, 1));
+        assert_eq!(cpu.run(&mut []), Stop::Breakpoint);
+        assert_eq!((cpu.cycle, cpu.x), (5, 4));
+        assert_eq!(cpu.run(&mut []), Stop::Halted);
+        assert_eq!((cpu.cycle, cpu.x), (6, -1));
+    }
+
+    #[test]
+    fn test_assemble_text() {
+        for text in ["BACEFGHJ", "EKLOPRSU", "ZUSRPOLK", "F A  B", "RGZEHURK"] {
+            let program = assemble_text(text).unwrap();
+            let (crt, _) = run(program);
+            assert_eq!(crt.pixels, render_text(text).unwrap());
+        }
+        assert!(assemble_text("abc").is_err());
+        // The top-left two pixels are always lit
+        assert!(assemble_text("HELLO").is_err());
+        assert!(assemble_text("ABCEFGHJK").is_err());
+    }
+
+    #[test]
+    fn test_invalid_instructions() {
+        assert!(parse_program("noop 1").is_err());
+        assert!(parse_program("addx").is_err());
+        assert!(parse_program("mulx 2").is_err());
+    }
+}
@@ -1,26 +1,205 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
+use num::{BigUint, Integer, Zero};
 
-use std::{collections::VecDeque, str::FromStr};
+use std::{
+    collections::{HashMap, VecDeque},
+    str::FromStr,
+};
 
-#[derive(Debug, Default, Clone)]
-struct Operation {
-    op: char,
-    value: Option<u64>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
 }
 
-impl FromStr for Operation {
-    type Err = anyhow::Error;
+// The right hand side of `new = ...`, e.g. `old * old` or `(old + 3) * 2`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+enum Expr {
+    #[default]
+    Old,
+    Const(u64),
+    Binary(Box<Expr>, Op, Box<Expr>),
+}
 
-    fn from_str(s: &str) -> Result<Operation, Self::Err> {
-        let (op, value) = s.split_once(" ").unwrap();
-        Ok(Operation {
-            op: op.chars().next().unwrap(),
-            value: match value.parse::<u64>() {
-                Ok(v) => Some(v),
-                Err(_) => None,
-            },
+impl Expr {
+    /// Evaluate with `old` as the current worry level. With a modulus every intermediate
+    /// value is reduced, which keeps `+`, `-` and `*` exact modulo it but rules out `/`. A
+    /// negative worry level can't be spotted that way, so `-` wraps around where it would be
+    /// an error without a modulus. Without one, overflowing a u64 is an error rather than a
+    /// silently wrong answer.
+    fn eval(&self, old: u64, modulus: Option<u64>) -> Result<u64> {
+        let (a, op, b) = match self {
+            Expr::Old => return Ok(old),
+            Expr::Const(v) => return Ok(modulus.map_or(*v, |m| v % m)),
+            Expr::Binary(a, op, b) => (a.eval(old, modulus)?, op, b.eval(old, modulus)?),
+        };
+
+        Ok(match (op, modulus) {
+            (Op::Add, None) => a
+                .checked_add(b)
+                .ok_or_else(|| anyhow!("worry level overflowed: {} + {}", a, b))?,
+            (Op::Add, Some(m)) => ((a as u128 + b as u128) % m as u128) as u64,
+            (Op::Sub, None) => a
+                .checked_sub(b)
+                .ok_or_else(|| anyhow!("worry level went negative: {} - {}", a, b))?,
+            (Op::Sub, Some(m)) => {
+                let (a, b, m) = (a as u128, b as u128, m as u128);
+                ((a % m + m - b % m) % m) as u64
+            }
+            (Op::Mul, None) => a
+                .checked_mul(b)
+                .ok_or_else(|| anyhow!("worry level overflowed: {} * {}", a, b))?,
+            (Op::Mul, Some(m)) => ((a as u128 * b as u128) % m as u128) as u64,
+            (Op::Div, None) => a
+                .checked_div(b)
+                .ok_or_else(|| anyhow!("division by zero"))?,
+            (Op::Div, Some(_)) => bail!("division can't be evaluated under a modulus"),
+        })
+    }
+
+    // Exact evaluation for the big-integer mode, which never needs a modulus
+    fn eval_big(&self, old: &BigUint) -> Result<BigUint> {
+        let (a, op, b) = match self {
+            Expr::Old => return Ok(old.clone()),
+            Expr::Const(v) => return Ok(BigUint::from(*v)),
+            Expr::Binary(a, op, b) => (a.eval_big(old)?, op, b.eval_big(old)?),
+        };
+
+        Ok(match op {
+            Op::Add => a + b,
+            Op::Sub if a < b => bail!("worry level went negative: {} - {}", a, b),
+            Op::Sub => a - b,
+            Op::Mul => a * b,
+            Op::Div if b.is_zero() => bail!("division by zero"),
+            Op::Div => a / b,
         })
     }
+
+    fn uses_division(&self) -> bool {
+        match self {
+            Expr::Binary(a, op, b) => *op == Op::Div || a.uses_division() || b.uses_division(),
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = ExprParser {
+            input: s.as_bytes(),
+            pos: 0,
+        };
+        let expr = parser.expr()?;
+        parser.skip_whitespace();
+        if parser.pos < s.len() {
+            bail!(
+                "unexpected {:?} at column {} of {:?}",
+                &s[parser.pos..],
+                parser.pos + 1,
+                s
+            );
+        }
+        Ok(expr)
+    }
+}
+
+/*
+Recursive descent with the usual precedence:
+    expr   := term (('+' | '-') term)*
+    term   := factor (('*' | '/') factor)*
+    factor := 'old' | number | '(' expr ')'
+*/
+struct ExprParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl ExprParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self
+            .input
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.input.get(self.pos).copied()
+    }
+
+    fn error(&self, expected: &str) -> anyhow::Error {
+        let input = String::from_utf8_lossy(self.input);
+        match self.input.get(self.pos) {
+            Some(&c) => anyhow!(
+                "expected {} but found {:?} at column {} of {:?}",
+                expected,
+                c as char,
+                self.pos + 1,
+                input
+            ),
+            None => anyhow!("expected {} at the end of {:?}", expected, input),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let mut lhs = self.term()?;
+        while let Some(op) = match self.peek() {
+            Some(b'+') => Some(Op::Add),
+            Some(b'-') => Some(Op::Sub),
+            _ => None,
+        } {
+            self.pos += 1;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(self.term()?));
+        }
+        Ok(lhs)
+    }
+
+    fn term(&mut self) -> Result<Expr> {
+        let mut lhs = self.factor()?;
+        while let Some(op) = match self.peek() {
+            Some(b'*') => Some(Op::Mul),
+            Some(b'/') => Some(Op::Div),
+            _ => None,
+        } {
+            self.pos += 1;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(self.factor()?));
+        }
+        Ok(lhs)
+    }
+
+    fn factor(&mut self) -> Result<Expr> {
+        match self.peek() {
+            Some(b'(') => {
+                self.pos += 1;
+                let expr = self.expr()?;
+                if self.peek() != Some(b')') {
+                    return Err(self.error("')'"));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let start = self.pos;
+                while self.input.get(self.pos).is_some_and(|c| c.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+                let digits = std::str::from_utf8(&self.input[start..self.pos])?;
+                Ok(Expr::Const(digits.parse()?))
+            }
+            _ if self.input[self.pos..].starts_with(b"old") => {
+                self.pos += 3;
+                Ok(Expr::Old)
+            }
+            _ => Err(self.error("'old', a number or '('")),
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -33,7 +212,7 @@ struct Test {
 #[derive(Debug, Default, Clone)]
 struct Monkey {
     items: VecDeque<u64>,
-    operation: Operation,
+    operation: Expr,
     test: Test,
     inspected: u64,
 }
@@ -42,95 +221,301 @@ impl FromStr for Monkey {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Monkey, Self::Err> {
-        let mut monkey = Monkey::default();
-
-        for line in s.lines() {
-            if line.starts_with("  Starting items: ") {
-                monkey.items = line[18..]
-                    .split(", ")
-                    .map(|s| s.parse::<u64>().unwrap())
-                    .collect();
-            }
-            if line.starts_with("  Operation: new = old ") {
-                monkey.operation = line[23..].parse().unwrap();
-            }
-            if line.starts_with("  Test: divisible by ") {
-                monkey.test.divisor = line[21..].parse::<u32>().unwrap();
-            }
-            if line.starts_with("    If true: throw to monkey ") {
-                monkey.test.true_target = line[29..].parse::<usize>().unwrap();
-            }
-            if line.starts_with("    If false: throw to monkey ") {
-                monkey.test.false_target = line[30..].parse::<usize>().unwrap()
-            }
+        // Every line is required, rather than quietly defaulting to `old` or monkey 0
+        let field = |prefix: &str| {
+            s.lines()
+                .find_map(|line| line.strip_prefix(prefix))
+                .ok_or_else(|| anyhow!("missing {:?} line", prefix.trim()))
+        };
+
+        let items = field("  Starting items: ")?
+            .split(", ")
+            .map(|s| s.parse::<u64>())
+            .collect::<Result<_, _>>()?;
+        let operation = field("  Operation: new = ")?.parse()?;
+        let divisor = field("  Test: divisible by ")?.parse::<u32>()?;
+        if divisor == 0 {
+            bail!("test divisor must be positive");
         }
+        let true_target = field("    If true: throw to monkey ")?.parse::<usize>()?;
+        let false_target = field("    If false: throw to monkey ")?.parse::<usize>()?;
 
-        Ok(monkey)
+        Ok(Monkey {
+            items,
+            operation,
+            test: Test {
+                divisor,
+                true_target,
+                false_target,
+            },
+            inspected: 0,
+        })
     }
 }
 
-fn main() -> Result<()> {
-    let input = std::fs::read_to_string("./inputs/day11.txt").unwrap();
-    let part1_monkeys = input
+fn parse_monkeys(input: &str) -> Result<Vec<Monkey>> {
+    let monkeys = input
         .split("\n\n")
-        .map(|monkey| monkey.parse::<Monkey>().unwrap())
-        .collect::<Vec<_>>();
-    let part2_monkeys = part1_monkeys.clone();
+        .enumerate()
+        .map(|(i, monkey)| {
+            monkey
+                .parse::<Monkey>()
+                .with_context(|| format!("monkey {}", i))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    for (i, monkey) in monkeys.iter().enumerate() {
+        for to_monkey in [monkey.test.true_target, monkey.test.false_target] {
+            if to_monkey >= monkeys.len() {
+                bail!(
+                    "monkey {}: throws to monkey {}, but there are only {}",
+                    i,
+                    to_monkey,
+                    monkeys.len()
+                );
+            }
+        }
+    }
+    Ok(monkeys)
+}
+
+fn main() -> Result<()> {
+    let mut validate = None;
+    let mut rounds = None;
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.as_slice() {
+        [] => {}
+        [flag, n] if flag == "--validate" => validate = Some(n.parse::<u32>()?),
+        [flag, n] if flag == "--rounds" => rounds = Some(n.parse::<u64>()?),
+        _ => bail!("usage: day11 [--validate <rounds> | --rounds <rounds>]"),
+    }
 
-    let part1 = solution(part1_monkeys, 20, true);
-    let part2 = solution(part2_monkeys, 10_000, false);
+    let input = std::fs::read_to_string("./inputs/day11.txt")?;
+    let monkeys = parse_monkeys(&input)?;
+
+    if let Some(rounds) = rounds {
+        let inspected = simulate_cycles(&monkeys, rounds)?;
+        println!("Inspections after {} rounds: {:?}", rounds, inspected);
+        println!("Monkey business: {}", monkey_business(&inspected)?);
+        return Ok(());
+    }
+
+    if let Some(rounds) = validate {
+        // Part 2 rules, with and without the lcm trick
+        let modular = simulate(monkeys.clone(), rounds, false)?;
+        let exact = simulate_big(&monkeys, rounds, false)?;
+        println!("lcm reduction: {:?}", modular);
+        println!("big integers:  {:?}", exact);
+        if modular != exact {
+            bail!("inspection counts differ after {} rounds", rounds);
+        }
+        println!("inspection counts agree after {} rounds", rounds);
+        return Ok(());
+    }
+
+    let part1 = solution(monkeys.clone(), 20, true)?;
+    let part2 = solution(monkeys, 10_000, false)?;
     println!("Part 1: {:?}", part1);
     println!("Part 2: {:?}", part2);
     Ok(())
 }
 
-fn solution(mut monkeys: Vec<Monkey>, rounds: u32, worry_divisor: bool) -> u64 {
-    // Calculate the least common multiple of the test divisors or worry numbers go kaboom
-    let mut lcm = 1;
-    for i in 0..monkeys.len() {
-        lcm = (lcm * monkeys[i].test.divisor) / num::integer::gcd(lcm, monkeys[i].test.divisor);
+fn solution(monkeys: Vec<Monkey>, rounds: u32, worry_divisor: bool) -> Result<u128> {
+    monkey_business(&simulate(monkeys, rounds, worry_divisor)?)
+}
+
+fn monkey_business(inspected: &[u64]) -> Result<u128> {
+    let mut activity = inspected.to_vec();
+    activity.sort_by(|a, b| b.cmp(a));
+
+    match activity.as_slice() {
+        [a, b, ..] => Ok(*a as u128 * *b as u128),
+        _ => bail!("need at least two monkeys"),
+    }
+}
+
+fn target(monkey: &Monkey, divisible: bool, count: usize) -> Result<usize> {
+    let to_monkey = if divisible {
+        monkey.test.true_target
+    } else {
+        monkey.test.false_target
+    };
+    if to_monkey >= count {
+        bail!(
+            "throw to monkey {}, but there are only {}",
+            to_monkey,
+            count
+        );
+    }
+    Ok(to_monkey)
+}
+
+// Calculate the least common multiple of the test divisors or worry numbers go kaboom
+fn divisor_lcm(monkeys: &[Monkey]) -> Result<u64> {
+    let mut lcm = 1u64;
+    for monkey in monkeys.iter() {
+        let divisor = monkey.test.divisor as u64;
+        lcm = (lcm / num::integer::gcd(lcm, divisor))
+            .checked_mul(divisor)
+            .ok_or_else(|| anyhow!("the lcm of the test divisors overflows a u64"))?;
+    }
+    Ok(lcm)
+}
+
+/// Inspection counts per monkey after `rounds` rounds.
+fn simulate(mut monkeys: Vec<Monkey>, rounds: u32, worry_divisor: bool) -> Result<Vec<u64>> {
+    // Without the divide-by-3 relief worries are only tracked modulo the lcm
+    let modulus = if worry_divisor {
+        None
+    } else {
+        Some(divisor_lcm(&monkeys)?)
+    };
+    if modulus.is_some()
+        && monkeys
+            .iter()
+            .any(|monkey| monkey.operation.uses_division())
+    {
+        bail!("operations using '/' can't be combined with the lcm reduction");
     }
 
     for _ in 0..rounds {
         for i in 0..monkeys.len() {
-            while !monkeys[i].items.is_empty() {
-                let mut item = monkeys[i].items.pop_front().unwrap();
+            while let Some(item) = monkeys[i].items.pop_front() {
                 monkeys[i].inspected += 1;
-                let worry = match monkeys[i].operation.value {
-                    Some(v) => v,
-                    None => item,
-                };
-
-                let new_item = match monkeys[i].operation.op {
-                    '+' => item + worry,
-                    '*' => item * worry,
-                    _ => panic!("unknown operation"),
-                };
 
+                let mut item = monkeys[i].operation.eval(item, modulus)?;
                 if worry_divisor {
-                    item = new_item / 3;
-                } else {
-                    item = new_item % lcm as u64;
+                    item /= 3;
                 }
 
-                let to_monkey = if item % monkeys[i].test.divisor as u64 == 0 {
-                    monkeys[i].test.true_target
-                } else {
-                    monkeys[i].test.false_target
-                };
-
+                let divisible = item % monkeys[i].test.divisor as u64 == 0;
+                let to_monkey = target(&monkeys[i], divisible, monkeys.len())?;
                 monkeys[to_monkey].items.push_back(item);
             }
         }
     }
 
-    let mut activity = monkeys
+    Ok(monkeys.iter().map(|monkey| monkey.inspected).collect())
+}
+
+/*
+Part 2 rules for any number of rounds. Items never affect each other, so each one is followed on
+its own. At the start of a round an item is fully described by (monkey holding it, worry mod
+lcm), and since there are finitely many of those its path is eventually periodic. Once a start
+state repeats, the inspections of one period are multiplied out over the remaining rounds.
+*/
+fn simulate_cycles(monkeys: &[Monkey], rounds: u64) -> Result<Vec<u64>> {
+    let lcm = divisor_lcm(monkeys)?;
+    if monkeys
         .iter()
-        .map(|monkey| monkey.inspected)
-        .collect::<Vec<_>>();
-    activity.sort_by(|a, b| b.cmp(a));
+        .any(|monkey| monkey.operation.uses_division())
+    {
+        bail!("operations using '/' can't be combined with the lcm reduction");
+    }
+
+    let n = monkeys.len();
+    let mut inspected = vec![0u64; n];
+    let overflow = |m: usize| anyhow!("monkey {}'s inspection count overflows a u64", m);
+
+    for (start, monkey) in monkeys.iter().enumerate() {
+        for &item in monkey.items.iter() {
+            let mut state = (start, item % lcm);
+            // Round each state was first seen in, and inspections per monkey before each round
+            let mut seen = HashMap::new();
+            let mut prefix = vec![vec![0u64; n]];
+
+            let mut round = 0u64;
+            let cycle_start = loop {
+                if round == rounds {
+                    break None;
+                }
+                if let Some(&first) = seen.get(&state) {
+                    break Some(first);
+                }
+                seen.insert(state, round);
+
+                let mut counts = prefix[round as usize].clone();
+                let (mut at, mut worry) = state;
+                // A throw to a later monkey is handled again in the same round
+                loop {
+                    counts[at] += 1;
+                    worry = monkeys[at].operation.eval(worry, Some(lcm))?;
+                    let divisible = worry % monkeys[at].test.divisor as u64 == 0;
+                    let to_monkey = target(&monkeys[at], divisible, n)?;
+                    if to_monkey == at {
+                        bail!("monkey {} throws to itself", at);
+                    }
+                    if to_monkey < at {
+                        state = (to_monkey, worry);
+                        break;
+                    }
+                    at = to_monkey;
+                }
+                prefix.push(counts);
+                round += 1;
+            };
+
+            let totals = match cycle_start {
+                None => prefix[round as usize].clone(),
+                Some(first) => {
+                    let period = round - first;
+                    let (cycles, rest) = ((rounds - first) / period, (rounds - first) % period);
+                    let at = |r: u64| &prefix[r as usize];
+                    (0..n)
+                        .map(|m| {
+                            cycles
+                                .checked_mul(at(round)[m] - at(first)[m])
+                                .and_then(|count| count.checked_add(at(first + rest)[m]))
+                                .ok_or_else(|| overflow(m))
+                        })
+                        .collect::<Result<Vec<_>>>()?
+                }
+            };
+            for (m, (total, count)) in inspected.iter_mut().zip(totals).enumerate() {
+                *total = total.checked_add(count).ok_or_else(|| overflow(m))?;
+            }
+        }
+    }
 
-    activity[0] * activity[1]
+    Ok(inspected)
+}
+
+/*
+The same simulation on exact big integers, without the lcm trick. Worry levels grow
+exponentially with `old * old` monkeys, so this is only practical for small round counts, but
+it is a useful check that the modular answer is right.
+*/
+fn simulate_big(monkeys: &[Monkey], rounds: u32, worry_divisor: bool) -> Result<Vec<u64>> {
+    let mut items = monkeys
+        .iter()
+        .map(|monkey| {
+            monkey
+                .items
+                .iter()
+                .map(|&item| BigUint::from(item))
+                .collect()
+        })
+        .collect::<Vec<VecDeque<_>>>();
+    let mut inspected = vec![0u64; monkeys.len()];
+    let three = BigUint::from(3u32);
+
+    for _ in 0..rounds {
+        for (i, monkey) in monkeys.iter().enumerate() {
+            while let Some(item) = items[i].pop_front() {
+                inspected[i] += 1;
+
+                let mut item = monkey.operation.eval_big(&item)?;
+                if worry_divisor {
+                    item /= &three;
+                }
+
+                let divisible = item.is_multiple_of(&BigUint::from(monkey.test.divisor));
+                items[target(monkey, divisible, monkeys.len())?].push_back(item);
+            }
+        }
+    }
+
+    Ok(inspected)
 }
 
 #[allow(dead_code)]
@@ -161,3 +546,174 @@ Monkey 3:
   Test: divisible by 17
     If true: throw to monkey 0
     If false: throw to monkey 1"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aoc2022::generate;
+    use proptest::prelude::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn generated(seed: u64, count: usize) -> Vec<Monkey> {
+        let input = generate::day11(&mut StdRng::seed_from_u64(seed), count);
+        parse_monkeys(input.trim_end()).unwrap()
+    }
+
+    #[test]
+    fn test_solution() {
+        let monkeys = parse_monkeys(TEST_INPUT).unwrap();
+        assert_eq!(solution(monkeys.clone(), 20, true).unwrap(), 10605);
+        assert_eq!(solution(monkeys, 10_000, false).unwrap(), 2713310158);
+    }
+
+    #[test]
+    fn test_expressions() {
+        let eval = |s: &str, old, modulus| s.parse::<Expr>().unwrap().eval(old, modulus).unwrap();
+
+        assert_eq!(eval("old * old", 7, None), 49);
+        assert_eq!(eval("3 * old + 1", 7, None), 22);
+        assert_eq!(eval("(old + 3) * (2 - 1)", 7, None), 10);
+        assert_eq!(eval("old - 2 - 3", 10, None), 5);
+        assert_eq!(eval("old / 2 * 2", 7, None), 6);
+        assert_eq!(eval("old * old", u64::MAX - 1, Some(u64::MAX)), 1);
+
+        assert_eq!(eval("old - 10", 7, Some(13)), 10);
+        assert_eq!(eval("old - 20", 7, Some(13)), 0);
+        assert!("old - 10".parse::<Expr>().unwrap().eval(7, None).is_err());
+        assert!("old / 2"
+            .parse::<Expr>()
+            .unwrap()
+            .eval(7, Some(13))
+            .is_err());
+        for invalid in ["", "old +", "(old", "old old", "new", "2 ** old"] {
+            assert!(invalid.parse::<Expr>().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_big_integers_agree() {
+        let monkeys = parse_monkeys(TEST_INPUT).unwrap();
+        assert_eq!(
+            monkey_business(&simulate_big(&monkeys, 20, true).unwrap()).unwrap(),
+            10605
+        );
+        assert_eq!(
+            simulate_big(&monkeys, 30, false).unwrap(),
+            simulate(monkeys, 30, false).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_cycles() {
+        let monkeys = parse_monkeys(TEST_INPUT).unwrap();
+        for rounds in [1, 20, 1000] {
+            assert_eq!(
+                simulate_cycles(&monkeys, rounds).unwrap(),
+                simulate(monkeys.clone(), rounds as u32, false).unwrap()
+            );
+        }
+        assert_eq!(
+            monkey_business(&simulate_cycles(&monkeys, 10_000).unwrap()).unwrap(),
+            2713310158
+        );
+        assert!(simulate_cycles(&monkeys, 1_000_000_000_000).is_ok());
+        assert!(simulate_cycles(&monkeys, 18_000_000_000_000_000_000).is_err());
+    }
+
+    #[test]
+    fn test_overflow() {
+        // Worries square every round with no relief
+        let mut monkeys = parse_monkeys(TEST_INPUT).unwrap();
+        for monkey in monkeys.iter_mut() {
+            monkey.operation = "old * old + 2".parse().unwrap();
+        }
+        assert!(simulate(monkeys.clone(), 20, true).is_err());
+        assert!(simulate_big(&monkeys, 5, true).is_ok());
+
+        // Pairwise coprime divisors whose product doesn't fit in a u64
+        for (monkey, divisor) in monkeys.iter_mut().zip([4294967291, 4294967279, 4294967231]) {
+            monkey.test.divisor = divisor;
+        }
+        assert!(simulate(monkeys, 1, false).is_err());
+    }
+
+    #[test]
+    fn test_modular_subtraction() {
+        // Worries stay well above 3 with `old * old - 3`, so exact mode agrees with the lcm
+        let mut monkeys = parse_monkeys(TEST_INPUT).unwrap();
+        monkeys[2].operation = "old * old - 3".parse().unwrap();
+        let modular = simulate(monkeys.clone(), 10, false).unwrap();
+        assert_eq!(modular, simulate_big(&monkeys, 10, false).unwrap());
+        assert_eq!(simulate_cycles(&monkeys, 10).unwrap(), modular);
+
+        monkeys[3].operation = "old / 2".parse().unwrap();
+        assert!(simulate(monkeys.clone(), 10, false).is_err());
+        assert!(simulate_cycles(&monkeys, 10).is_err());
+    }
+
+    #[test]
+    fn test_invalid_divisors() {
+        let zero = TEST_INPUT.replace("divisible by 13", "divisible by 0");
+        assert!(parse_monkeys(&zero).is_err());
+        let missing = TEST_INPUT.replace("  Test: divisible by 13\n", "");
+        assert!(parse_monkeys(&missing).is_err());
+    }
+
+    #[test]
+    fn test_missing_lines() {
+        for line in [
+            "  Starting items: 54, 65, 75, 74\n",
+            "  Operation: new = old + 6\n",
+            "    If true: throw to monkey 2\n",
+            "    If false: throw to monkey 0",
+        ] {
+            let input = TEST_INPUT.replacen(line, "", 1);
+            let message = format!("{:#}", parse_monkeys(&input).unwrap_err());
+            assert!(message.starts_with("monkey ") && message.contains(": missing"));
+        }
+
+        let input = TEST_INPUT.replace("throw to monkey 3", "throw to monkey 4");
+        assert!(parse_monkeys(&input).is_err());
+    }
+
+    // Exact worry levels double in length every time an item meets the squaring monkey, so the
+    // big integer runs are kept to a handful of rounds
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn relief_matches_big_integers(seed in any::<u64>(), count in 3..=8usize) {
+            let monkeys = generated(seed, count);
+            prop_assert_eq!(
+                simulate(monkeys.clone(), 20, true).unwrap(),
+                simulate_big(&monkeys, 20, true).unwrap()
+            );
+        }
+
+        #[test]
+        fn lcm_matches_big_integers(
+            seed in any::<u64>(),
+            count in 3..=8usize,
+            rounds in 0..=12u32,
+        ) {
+            let monkeys = generated(seed, count);
+            prop_assert_eq!(
+                simulate(monkeys.clone(), rounds, false).unwrap(),
+                simulate_big(&monkeys, rounds, false).unwrap()
+            );
+        }
+
+        #[test]
+        fn cycles_match_simulation(
+            seed in any::<u64>(),
+            count in 3..=8usize,
+            rounds in 0..=500u32,
+        ) {
+            let monkeys = generated(seed, count);
+            prop_assert_eq!(
+                simulate_cycles(&monkeys, rounds as u64).unwrap(),
+                simulate(monkeys, rounds, false).unwrap()
+            );
+        }
+    }
+}
@@ -1,125 +1,255 @@
-use anyhow::Result;
-use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::str::FromStr;
 
-/*
-Only needed to implement ordering for the BinaryHeap, since tuples already have Ord implemented.
-Could just use a regular tuple and the std::cmp::Reverse() function, but why not do extra work..
-*/
-#[derive(Eq)]
-struct Loc(i32, (usize, usize));
+use anyhow::{anyhow, bail, Context, Result};
 
-impl Ord for Loc {
-    fn cmp(&self, other: &Self) -> Ordering {
-        other.0.cmp(&self.0)
+// Width of each distance bucket in the histogram
+const HISTOGRAM_BUCKET: u32 = 50;
+
+fn main() -> Result<()> {
+    let mut show_route = false;
+    let mut show_histogram = false;
+    let mut ppm = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--route" => show_route = true,
+            "--histogram" => show_histogram = true,
+            "--ppm" => ppm = Some(args.next().context("--ppm needs a file name")?),
+            _ => bail!("usage: day12 [--route] [--histogram] [--ppm <file>]"),
+        }
     }
-}
 
-impl PartialOrd for Loc {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    let input = std::fs::read_to_string("./inputs/day12.txt")?;
+    let map = input.parse::<Heightmap>()?;
+    let dist = map.distances_to_end();
+
+    let route = map
+        .route(map.start, &dist)
+        .ok_or_else(|| anyhow!("E cannot be reached from S"))?;
+    println!("Part 1: {}", route.len() - 1);
+
+    let (best, steps) = map
+        .best_start(&dist)
+        .ok_or_else(|| anyhow!("E cannot be reached from any 'a'"))?;
+    println!("Part 2: {} (from row {}, col {})", steps, best.0, best.1);
+
+    if show_route {
+        print!("{}", map.render_route(&route));
     }
+    if show_histogram {
+        print!("{}", histogram(&dist, HISTOGRAM_BUCKET));
+    }
+    if let Some(file) = ppm {
+        std::fs::write(&file, map.to_ppm(&route))
+            .with_context(|| format!("could not write {}", file))?;
+    }
+
+    Ok(())
 }
 
-impl PartialEq for Loc {
-    fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0
-    }
+type Pos = (usize, usize);
+
+#[derive(Debug)]
+struct Heightmap {
+    // Elevations 0-25, with S at 'a' and E at 'z'
+    heights: Vec<Vec<u8>>,
+    start: Pos,
+    end: Pos,
 }
 
-fn main() -> Result<()> {
-    let input = std::fs::read_to_string("./inputs/day12.txt").unwrap();
-
-    let mut start = (0, 0);
-    let mut end = (0, 0);
-    let mut heightmap = vec![];
-
-    for (row, line) in input.lines().enumerate() {
-        heightmap.push(vec![]);
-        for (col, c) in line.chars().enumerate() {
-            // Convert chars to 0-25, with 'S' = 0 and 'E' = 25
-            if c == 'S' {
-                start = (row, col);
-                heightmap[row].push('a' as i32 - 'a' as i32);
-            } else if c == 'E' {
-                end = (row, col);
-                heightmap[row].push('z' as i32 - 'a' as i32);
-            } else {
-                heightmap[row].push(c as i32 - 'a' as i32);
+impl FromStr for Heightmap {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut start = None;
+        let mut end = None;
+        let mut heights: Vec<Vec<u8>> = vec![];
+
+        for (row, line) in s.lines().enumerate() {
+            let mut heights_row = vec![];
+            for (col, c) in line.chars().enumerate() {
+                let height = match c {
+                    'S' => {
+                        if let Some((r, c)) = start {
+                            bail!(
+                                "second start S at row {}, col {} (the first is at row {}, col {})",
+                                row + 1,
+                                col + 1,
+                                r + 1,
+                                c + 1
+                            );
+                        }
+                        start = Some((row, col));
+                        'a'
+                    }
+                    'E' => {
+                        if let Some((r, c)) = end {
+                            bail!(
+                                "second end E at row {}, col {} (the first is at row {}, col {})",
+                                row + 1,
+                                col + 1,
+                                r + 1,
+                                c + 1
+                            );
+                        }
+                        end = Some((row, col));
+                        'z'
+                    }
+                    'a'..='z' => c,
+                    _ => bail!("unexpected {:?} at row {}, col {}", c, row + 1, col + 1),
+                };
+                heights_row.push(height as u8 - b'a');
             }
+            if !heights.is_empty() && heights_row.len() != heights[0].len() {
+                bail!("row {} is not the same width as the first row", row + 1);
+            }
+            heights.push(heights_row);
         }
-    }
 
-    let part1 = dijkstras(start, end, &heightmap, true);
-    println!("Part 1: {}", part1);
-    let part2 = dijkstras(start, end, &heightmap, false);
-    println!("Part 2: {}", part2);
-    Ok(())
+        Ok(Heightmap {
+            heights,
+            start: start.ok_or_else(|| anyhow!("no start S in the heightmap"))?,
+            end: end.ok_or_else(|| anyhow!("no end E in the heightmap"))?,
+        })
+    }
 }
 
-fn neighbors(i: usize, j: usize, heightmap: &Vec<Vec<i32>>, is_part1: bool) -> Vec<(usize, usize)> {
-    let mut neighbors = vec![];
+impl Heightmap {
+    fn height(&self, (row, col): Pos) -> u8 {
+        self.heights[row][col]
+    }
 
-    for (di, dj) in vec![(0, 1), (0, -1), (1, 0), (-1, 0)].into_iter() {
-        let ni: i32 = i as i32 + di;
-        let nj: i32 = j as i32 + dj;
+    fn neighbors(&self, (row, col): Pos) -> impl Iterator<Item = Pos> + '_ {
+        let (rows, cols) = (self.heights.len(), self.heights[0].len());
+        [
+            row.checked_sub(1).map(|r| (r, col)),
+            (row + 1 < rows).then_some((row + 1, col)),
+            col.checked_sub(1).map(|c| (row, c)),
+            (col + 1 < cols).then_some((row, col + 1)),
+        ]
+        .into_iter()
+        .flatten()
+    }
 
-        if ni < 0 || ni >= heightmap.len() as i32 || nj < 0 || nj >= heightmap[0].len() as i32 {
-            continue;
-        }
+    // A step can climb at most one level, but can drop any amount
+    fn can_step(&self, from: Pos, to: Pos) -> bool {
+        self.height(to) <= self.height(from) + 1
+    }
 
-        // For part 1, we go from S -> E location.
-        // For part 2 we go in reverse from E down to all locations with an elevation of 'a'
-        if is_part1 {
-            if heightmap[ni as usize][nj as usize] <= heightmap[i][j] + 1 {
-                neighbors.push((ni as usize, nj as usize));
-            }
-        } else {
-            if heightmap[ni as usize][nj as usize] >= heightmap[i][j] - 1 {
-                neighbors.push((ni as usize, nj as usize));
+    /// Steps needed to reach E from every cell, or None where E can't be reached.
+    ///
+    /// Every step costs the same, so this is a plain BFS outwards from E with the climbing rule
+    /// reversed.
+    fn distances_to_end(&self) -> Vec<Vec<Option<u32>>> {
+        let mut dist = vec![vec![None; self.heights[0].len()]; self.heights.len()];
+        dist[self.end.0][self.end.1] = Some(0);
+        let mut queue = VecDeque::from([(self.end, 0)]);
+
+        while let Some((pos, steps)) = queue.pop_front() {
+            for (r, c) in self.neighbors(pos) {
+                if dist[r][c].is_none() && self.can_step((r, c), pos) {
+                    dist[r][c] = Some(steps + 1);
+                    queue.push_back(((r, c), steps + 1));
+                }
             }
         }
+        dist
     }
-    neighbors
-}
 
-fn dijkstras(
-    start: (usize, usize),
-    end: (usize, usize),
-    heightmap: &Vec<Vec<i32>>,
-    is_part1: bool,
-) -> i32 {
-    let mut visited = vec![vec![false; heightmap[0].len()]; heightmap.len()];
-    let mut pq = BinaryHeap::new();
+    /// A shortest route from `from` to E, including both ends.
+    fn route(&self, from: Pos, dist: &[Vec<Option<u32>>]) -> Option<Vec<Pos>> {
+        let mut steps = dist[from.0][from.1]?;
+        let mut route = vec![from];
+        let mut pos = from;
 
-    if is_part1 {
-        pq.push(Loc(0, start));
-    } else {
-        pq.push(Loc(0, end));
+        // Any reachable neighbor one step closer to E keeps the route shortest
+        while steps > 0 {
+            pos = self
+                .neighbors(pos)
+                .find(|&(r, c)| dist[r][c] == Some(steps - 1) && self.can_step(pos, (r, c)))?;
+            route.push(pos);
+            steps -= 1;
+        }
+        Some(route)
+    }
+
+    /// The lowest cell with the shortest way to E, and how many steps that takes.
+    fn best_start(&self, dist: &[Vec<Option<u32>>]) -> Option<(Pos, u32)> {
+        self.heights
+            .iter()
+            .enumerate()
+            .flat_map(|(r, row)| (0..row.len()).map(move |c| (r, c)))
+            .filter(|&pos| self.height(pos) == 0)
+            .filter_map(|(r, c)| dist[r][c].map(|steps| ((r, c), steps)))
+            .min_by_key(|&(_, steps)| steps)
     }
 
-    while let Some(Loc(step_cnt, (i, j))) = pq.pop() {
-        if visited[i][j] {
-            continue;
+    /// The route drawn as arrows the way the puzzle description does, with E marking the end.
+    fn render_route(&self, route: &[Pos]) -> String {
+        let mut grid = vec![vec!['.'; self.heights[0].len()]; self.heights.len()];
+        for step in route.windows(2) {
+            let ((r0, c0), (r1, c1)) = (step[0], step[1]);
+            grid[r0][c0] = match (r1 as i64 - r0 as i64, c1 as i64 - c0 as i64) {
+                (-1, _) => '^',
+                (1, _) => 'v',
+                (_, -1) => '<',
+                _ => '>',
+            };
         }
-        visited[i][j] = true;
+        grid[self.end.0][self.end.1] = 'E';
 
-        if is_part1 {
-            if (i, j) == end {
-                return step_cnt;
-            }
-        } else {
-            // for part 2 we want any location with an elevation of 'a' (0)
-            if heightmap[i][j] == 0 {
-                return step_cnt;
+        grid.iter()
+            .map(|row| row.iter().collect::<String>() + "\n")
+            .collect()
+    }
+
+    /// The heightmap as a binary PPM image, one pixel per cell, in grey from dark valleys to
+    /// bright peaks with the route overlaid in red.
+    fn to_ppm(&self, route: &[Pos]) -> Vec<u8> {
+        let (rows, cols) = (self.heights.len(), self.heights[0].len());
+        let mut image = format!("P6\n{} {}\n255\n", cols, rows).into_bytes();
+        let header = image.len();
+
+        for row in self.heights.iter() {
+            for &height in row.iter() {
+                let grey = (height as u32 * 255 / 25) as u8;
+                image.extend([grey, grey, grey]);
             }
         }
-        for (ni, nj) in neighbors(i, j, heightmap, is_part1) {
-            pq.push(Loc(step_cnt + 1, (ni, nj)));
+        for &(r, c) in route.iter() {
+            let pixel = header + 3 * (r * cols + c);
+            image[pixel..pixel + 3].copy_from_slice(&[255, 0, 0]);
+        }
+        image
+    }
+}
+
+/// How many cells are each range of distances from E, plus how many can't reach it at all.
+fn histogram(dist: &[Vec<Option<u32>>], bucket: u32) -> String {
+    let mut counts = vec![];
+    let mut unreachable = 0;
+    for &cell in dist.iter().flatten() {
+        match cell {
+            Some(steps) => {
+                let i = (steps / bucket) as usize;
+                if i >= counts.len() {
+                    counts.resize(i + 1, 0);
+                }
+                counts[i] += 1;
+            }
+            None => unreachable += 1,
         }
     }
 
-    0
+    let mut out = String::new();
+    for (i, count) in counts.iter().enumerate() {
+        let low = i as u32 * bucket;
+        writeln!(out, "{:>5}-{:<5} {}", low, low + bucket - 1, count).unwrap();
+    }
+    writeln!(out, "unreachable {}", unreachable).unwrap();
+    out
 }
 
 #[allow(dead_code)]
@@ -128,3 +258,60 @@ abcryxxl
 accszExk
 acctuvwj
 abdefghi"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_part1() {
+        let map = TEST_INPUT.parse::<Heightmap>().unwrap();
+        let dist = map.distances_to_end();
+        let route = map.route(map.start, &dist).unwrap();
+        assert_eq!(route.len() - 1, 31);
+        assert_eq!(route.first(), Some(&map.start));
+        assert_eq!(route.last(), Some(&map.end));
+        assert!(route.windows(2).all(|step| map.can_step(step[0], step[1])));
+    }
+
+    #[test]
+    fn test_part2() {
+        let map = TEST_INPUT.parse::<Heightmap>().unwrap();
+        let dist = map.distances_to_end();
+        assert_eq!(map.best_start(&dist), Some(((4, 0), 29)));
+    }
+
+    #[test]
+    fn test_start_and_end() {
+        assert!("SaE".parse::<Heightmap>().is_ok());
+        assert!("aaE".parse::<Heightmap>().is_err());
+        assert!("Saa".parse::<Heightmap>().is_err());
+        let err = "SaE\naSa".parse::<Heightmap>().unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("second start S at row 2, col 2"));
+        let err = "SEE".parse::<Heightmap>().unwrap_err();
+        assert!(err.to_string().starts_with("second end E at row 1, col 3"));
+    }
+
+    #[test]
+    fn test_visuals() {
+        let map = TEST_INPUT.parse::<Heightmap>().unwrap();
+        let dist = map.distances_to_end();
+        let route = map.route(map.start, &dist).unwrap();
+
+        let rendered = map.render_route(&route);
+        assert_eq!(rendered.lines().count(), 5);
+        assert_eq!(rendered.chars().filter(|c| "^v<>".contains(*c)).count(), 31);
+        assert_eq!(rendered.find('E'), Some(2 * 9 + 5));
+
+        assert_eq!(
+            histogram(&dist, 10),
+            "    0-9     10\n   10-19    10\n   20-29    15\n   30-39    5\nunreachable 0\n"
+        );
+
+        let image = map.to_ppm(&route);
+        assert!(image.starts_with(b"P6\n8 5\n255\n"));
+        assert_eq!(image.len(), 11 + 8 * 5 * 3);
+    }
+}
@@ -1,32 +1,43 @@
 use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
 
-use anyhow::Result;
-use serde_json::{json, Value};
+use anyhow::{anyhow, Context, Result};
 
 fn main() -> Result<()> {
     let input = std::fs::read_to_string("./inputs/day13.txt")?;
 
-    println!("Part 1: {:?}", part1(&input).unwrap());
-    println!("Part 2: {:?}", part2(&input).unwrap());
+    println!("Part 1: {:?}", part1(&input)?);
+    println!("Part 2: {:?}", part2(&input)?);
 
     Ok(())
 }
 
+fn parse_line(line: &str, number: usize) -> Result<Packet> {
+    line.parse::<Packet>()
+        .with_context(|| format!("invalid packet on line {}", number + 1))
+}
+
 fn part1(input: &str) -> Result<usize> {
-    let packet_pairs = input
-        .split("\n\n")
-        .map(|line| {
-            let (left, right) = line.split_once("\n").unwrap_or_default();
-            (left, right)
-        })
-        .collect::<Vec<_>>();
+    let lines = input.lines().collect::<Vec<_>>();
 
     let mut sum_of_inorder = 0;
-    for (i, packet) in packet_pairs.iter().enumerate() {
-        let left: Value = serde_json::from_str(packet.0).expect("invalid json");
-        let right: Value = serde_json::from_str(packet.1).expect("invalid json");
+    for (i, pair) in lines.chunks(3).enumerate() {
+        let (left, right) = match pair {
+            [left, right, rest @ ..] => {
+                // Pairs are separated by a blank line, so a packet here means one went missing
+                if rest.first().is_some_and(|separator| !separator.is_empty()) {
+                    return Err(anyhow!(
+                        "expected a blank line between packet pairs on line {}",
+                        3 * i + 3
+                    ));
+                }
+                (parse_line(left, 3 * i)?, parse_line(right, 3 * i + 1)?)
+            }
+            _ => return Err(anyhow!("packet pair {} is missing its right packet", i + 1)),
+        };
 
-        if compare(left, right) == Ordering::Less {
+        if left < right {
             sum_of_inorder += i + 1;
         }
     }
@@ -34,72 +45,168 @@ fn part1(input: &str) -> Result<usize> {
 }
 
 fn part2(input: &str) -> Result<usize> {
-    let mut packets: Vec<Value> = vec![];
+    let mut packets = vec![];
 
-    for line in input.lines() {
+    for (i, line) in input.lines().enumerate() {
         if !line.is_empty() {
-            packets.push(serde_json::from_str(line).expect("invalid json"));
+            packets.push(parse_line(line, i)?);
         }
     }
     // Add the special dividers
-    let divider_one: Value = serde_json::from_str("[[2]]").expect("invalid json");
-    let divider_two: Value = serde_json::from_str("[[6]]").expect("invalid json");
+    let divider_one = Packet::List(vec![Packet::List(vec![Packet::Int(2)])]);
+    let divider_two = Packet::List(vec![Packet::List(vec![Packet::Int(6)])]);
     packets.push(divider_one.clone());
     packets.push(divider_two.clone());
 
-    packets.sort_by(|left, right| compare(left.clone(), right.clone()));
+    packets.sort();
+
+    // Positions are 1-based, and each divider sits after every packet that sorts before it
+    let position = |divider: &Packet| packets.partition_point(|packet| packet < divider) + 1;
+    Ok(position(&divider_one) * position(&divider_two))
+}
+
+#[derive(Debug, Clone)]
+enum Packet {
+    Int(u32),
+    List(Vec<Packet>),
+}
 
-    let mut decoder_key = 1;
-    for (i, packet) in packets.iter().enumerate() {
-        if packet == &divider_one || packet == &divider_two {
-            decoder_key *= i + 1;
+/*
+Lists compare element by element with the shorter list first on a tie, which is exactly how
+slices are ordered. An integer compared with a list is treated as a one element list, borrowed
+in place with slice::from_ref rather than built.
+*/
+impl Ord for Packet {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Packet::Int(a), Packet::Int(b)) => a.cmp(b),
+            (Packet::List(a), Packet::List(b)) => a.as_slice().cmp(b.as_slice()),
+            (Packet::Int(_), Packet::List(b)) => std::slice::from_ref(self).cmp(b.as_slice()),
+            (Packet::List(a), Packet::Int(_)) => a.as_slice().cmp(std::slice::from_ref(other)),
         }
     }
-    Ok(decoder_key)
 }
 
-fn compare(mut left: Value, mut right: Value) -> std::cmp::Ordering {
-    if left.is_array() && right.is_number() {
-        right = json!([&right]);
+impl PartialOrd for Packet {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
+}
 
-    if left.is_number() && right.is_array() {
-        left = json!([&left]);
+// Equality has to agree with the ordering, so 2, [2] and [[2]] are all equal
+impl PartialEq for Packet {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
     }
+}
+
+impl Eq for Packet {}
 
-    if left.is_number() && right.is_number() {
-        if left.as_u64().unwrap() < right.as_u64().unwrap() {
-            return Ordering::Less;
-        } else if left.as_u64().unwrap() == right.as_u64().unwrap() {
-            return Ordering::Equal;
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Packet::Int(v) => write!(f, "{}", v),
+            Packet::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
         }
+    }
+}
 
-        return Ordering::Greater;
+impl FromStr for Packet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = PacketParser {
+            input: s.as_bytes(),
+            pos: 0,
+        };
+        let packet = parser.packet()?;
+        if parser.pos < s.len() {
+            return Err(parser.error("end of packet"));
+        }
+        Ok(packet)
     }
+}
 
-    if left.is_array() && right.is_array() {
-        let mut i = 0;
+// Parses straight from the line's bytes, without going through an intermediate value type
+struct PacketParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
 
-        let l_len = left.as_array().unwrap().len();
-        let r_len = right.as_array().unwrap().len();
-        while i < l_len && i < r_len {
-            let x = compare(left[i].clone(), right[i].clone());
+impl PacketParser<'_> {
+    // Points at the offending column underneath the packet
+    fn error(&self, expected: &str) -> anyhow::Error {
+        let found = match self.input.get(self.pos) {
+            Some(&c) => format!("{:?}", c as char),
+            None => "end of line".to_string(),
+        };
+        anyhow!(
+            "expected {} but found {} at column {}\n{}\n{}^",
+            expected,
+            found,
+            self.pos + 1,
+            String::from_utf8_lossy(self.input),
+            " ".repeat(self.pos)
+        )
+    }
 
-            if x != Ordering::Equal {
-                return x;
-            }
+    fn packet(&mut self) -> Result<Packet> {
+        match self.input.get(self.pos) {
+            Some(b'[') => self.list(),
+            Some(c) if c.is_ascii_digit() => self.int(),
+            _ => Err(self.error("'[' or a digit")),
+        }
+    }
 
-            i += 1;
+    fn list(&mut self) -> Result<Packet> {
+        // Skip the '['
+        self.pos += 1;
+        let mut items = vec![];
+        if self.input.get(self.pos) == Some(&b']') {
+            self.pos += 1;
+            return Ok(Packet::List(items));
         }
-        if i == l_len {
-            if l_len == r_len {
-                return Ordering::Equal;
+
+        loop {
+            items.push(self.packet()?);
+            match self.input.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Packet::List(items));
+                }
+                _ => return Err(self.error("',' or ']'")),
             }
-            return Ordering::Less;
         }
-        return Ordering::Greater;
     }
-    Ordering::Greater
+
+    fn int(&mut self) -> Result<Packet> {
+        let start = self.pos;
+        let mut value = 0u32;
+        while let Some(&c) = self.input.get(self.pos).filter(|c| c.is_ascii_digit()) {
+            value = match value
+                .checked_mul(10)
+                .and_then(|v| v.checked_add((c - b'0') as u32))
+            {
+                Some(v) => v,
+                None => {
+                    self.pos = start;
+                    return Err(self.error("an integer that fits in a u32"));
+                }
+            };
+            self.pos += 1;
+        }
+        Ok(Packet::Int(value))
+    }
 }
 
 #[allow(dead_code)]
@@ -126,3 +233,64 @@ const TEST_INPUT: &str = r#"[1,1,3,1,1]
 
 [1,[2,[3,[4,[5,6,7]]]],8,9]
 [1,[2,[3,[4,[5,6,0]]]],8,9]"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parts() {
+        assert_eq!(part1(TEST_INPUT).unwrap(), 13);
+        assert_eq!(part2(TEST_INPUT).unwrap(), 140);
+    }
+
+    #[test]
+    fn test_missing_separator() {
+        assert_eq!(part1("[1]\n[2]\n\n[2]\n[1]\n").unwrap(), 1);
+        let error = part1("[1]\n[2]\n[2]\n[1]\n").unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "expected a blank line between packet pairs on line 3"
+        );
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        for line in TEST_INPUT.lines().filter(|line| !line.is_empty()) {
+            assert_eq!(line.parse::<Packet>().unwrap().to_string(), line);
+        }
+    }
+
+    #[test]
+    fn test_ordering() {
+        let packet = |s: &str| s.parse::<Packet>().unwrap();
+        assert_eq!(packet("[[2]]"), packet("2"));
+        assert!(packet("[[1],[2,3,4]]") < packet("[[1],4]"));
+        assert!(packet("[9]") > packet("[[8,7,6]]"));
+        assert!(packet("[]") < packet("[[]]"));
+    }
+
+    #[test]
+    fn test_malformed_packets() {
+        for (line, message) in [
+            (
+                "[1,2",
+                "expected ',' or ']' but found end of line at column 5",
+            ),
+            (
+                "[1,,2]",
+                "expected '[' or a digit but found ',' at column 4",
+            ),
+            ("[a]", "expected '[' or a digit but found 'a' at column 2"),
+            ("[1]]", "expected end of packet but found ']' at column 4"),
+            (
+                "",
+                "expected '[' or a digit but found end of line at column 1",
+            ),
+            ("[4294967296]", "expected an integer that fits in a u32"),
+        ] {
+            let error = line.parse::<Packet>().unwrap_err().to_string();
+            assert!(error.starts_with(message), "{:?}: {}", line, error);
+        }
+    }
+}
@@ -1,193 +1,245 @@
-use anyhow::Result;
-use std::collections::HashSet;
-
-#[derive(Debug, Clone, PartialEq)]
-struct Sand {
-    x: usize,
-    y: usize,
-    at_rest: bool,
-}
+use anyhow::{anyhow, bail, Context, Result};
 
-impl Sand {
-    fn new(x: usize, y: usize) -> Self {
-        Sand {
-            x,
-            y,
-            at_rest: false,
-        }
-    }
-}
+const SOURCE: (usize, usize) = (500, 0);
 
 fn main() -> Result<()> {
+    let verbose = match std::env::args().nth(1).as_deref() {
+        None => false,
+        Some("-v") => true,
+        Some(_) => bail!("usage: day14 [-v]"),
+    };
+
     let input = std::fs::read_to_string("./inputs/day14.txt")?;
+    let paths = parse_paths(&input)?;
+
+    let mut cave = Cave::new(&paths)?;
+    if verbose {
+        eprintln!(
+            "cave spans x {}..{}, deepest rock at y {}",
+            cave.x0,
+            cave.x0 + cave.width,
+            cave.max_y
+        );
+    }
+    let part1 = cave.fill(false, verbose);
+    if verbose {
+        eprint!("{}", cave.draw());
+    }
 
-    let mut max_y: usize = usize::MIN;
-    let rock_paths = input
+    // Part 2 keeps pouring onto the sand that already came to rest
+    let part2 = part1 + cave.fill(true, verbose);
+    if verbose {
+        eprint!("{}", cave.draw());
+    }
+
+    println!("Part 1: {}", part1);
+    println!("Part 2: {}", part2);
+    Ok(())
+}
+
+fn parse_paths(input: &str) -> Result<Vec<Vec<(usize, usize)>>> {
+    input
         .lines()
-        .map(|line| {
+        .enumerate()
+        .map(|(i, line)| {
             line.split(" -> ")
                 .map(|point| {
-                    let mut coords = point
-                        .split(',')
-                        .map(|coord| coord.parse::<usize>().unwrap());
-
-                    let x = coords.next().unwrap();
-                    let y = coords.next().unwrap();
-
-                    if y > max_y {
-                        max_y = y;
-                    }
-                    (x, y)
+                    let (x, y) = point
+                        .split_once(',')
+                        .ok_or_else(|| anyhow!("missing ',' in point {:?}", point))?;
+                    Ok((x.parse()?, y.parse()?))
                 })
-                .collect::<Vec<_>>()
+                .collect::<Result<Vec<_>>>()
+                .with_context(|| format!("invalid rock path on line {}", i + 1))
         })
-        .collect::<Vec<_>>();
-
-    let mut cave = HashSet::<(usize, usize)>::new();
-
-    for rock_path in rock_paths.iter() {
-        if rock_path.len() == 1 {
-            let (x, y) = rock_path[0];
-            cave.insert((x, y));
-            continue;
-        }
-
-        for i in 1..rock_path.len() {
-            let end = rock_path[i];
-            let start = rock_path[i - 1];
-            add_rocks(&mut cave, start, end);
-        }
-    }
-
-    let cave_part2 = cave.clone();
-
-    let sand_count_p1 = part1(max_y, cave);
-    println!("====================");
-    let sand_count_p2 = part2(max_y, cave_part2);
-    println!("Part 1: {}", sand_count_p1);
-    println!("Part 2: {}", sand_count_p2);
-    Ok(())
+        .collect()
 }
 
-fn part1(max_y: usize, mut cave: HashSet<(usize, usize)>) -> i32 {
-    let mut sand_count = 0;
-
-    while simulate_sand(max_y, &mut cave) {
-        sand_count += 1;
-    }
-    sand_count
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cell {
+    Air,
+    Rock,
+    Sand,
 }
 
-fn part2(max_y: usize, mut cave: HashSet<(usize, usize)>) -> i32 {
-    let mut sand_count = 0;
-
-    loop {
-        let sand = simulate_sand2(max_y, &mut cave);
-        println!("Adding sand at ({},{})", sand.x, sand.y);
-        cave.insert((sand.x, sand.y));
-
-        sand_count += 1;
-
-        if sand.x == 500 && sand.y == 0 {
-            break;
-        }
-    }
-
-    draw_cave(max_y, &cave);
-    sand_count
+/*
+Sand piles up at most one column wider per row it falls, so with the floor two rows below the
+deepest rock every grain stays within SOURCE.x +- (floor depth). The grid covers that and the rocks,
+which means neither part ever has to bounds check a column.
+*/
+#[derive(Debug)]
+struct Cave {
+    x0: usize,
+    width: usize,
+    max_y: usize,
+    // Rows 0..=max_y + 1; the floor at max_y + 2 is implicit
+    cells: Vec<Cell>,
+    // Where the previous grain fell through, from the source down to just above where it rested
+    path: Vec<(usize, usize)>,
 }
 
-fn simulate_sand(max_y: usize, cave: &mut HashSet<(usize, usize)>) -> bool {
-    println!("max_y: {}", max_y);
-
-    let mut sand = Sand::new(500, 0);
-
-    while sand.y < max_y {
-        // check down (0, +1)
-        if !cave.contains(&(sand.x, sand.y + 1)) {
-            sand.y += 1;
-            continue;
+impl Cave {
+    fn new(paths: &[Vec<(usize, usize)>]) -> Result<Self> {
+        let points = || paths.iter().flatten();
+        let max_y = points()
+            .map(|&(_, y)| y)
+            .max()
+            .ok_or_else(|| anyhow!("no rocks in the cave"))?;
+        let floor = max_y + 2;
+        if floor > SOURCE.0 {
+            bail!("a floor at y {} would spread sand past x 0", floor);
         }
-
-        // check left (-1, +1)
-        if !cave.contains(&(sand.x - 1, sand.y + 1)) {
-            sand.x -= 1;
-            sand.y += 1;
-            continue;
-        }
-
-        // check right (+1, +1)
-        if !cave.contains(&(sand.x + 1, sand.y + 1)) {
-            sand.x += 1;
-            sand.y += 1;
-            continue;
+        let x_min = points()
+            .map(|&(x, _)| x)
+            .chain([SOURCE.0 - floor])
+            .min()
+            .unwrap();
+        let x_max = points()
+            .map(|&(x, _)| x)
+            .chain([SOURCE.0 + floor])
+            .max()
+            .unwrap();
+
+        let mut cave = Cave {
+            x0: x_min,
+            width: x_max - x_min + 1,
+            max_y,
+            cells: vec![Cell::Air; (x_max - x_min + 1) * floor],
+            path: vec![SOURCE],
+        };
+
+        for path in paths {
+            for (i, &(x, y)) in path.iter().enumerate() {
+                let (px, py) = path[i.saturating_sub(1)];
+                if px != x && py != y {
+                    bail!("rock from {},{} to {},{} is not straight", px, py, x, y);
+                }
+                for ry in py.min(y)..=py.max(y) {
+                    for rx in px.min(x)..=px.max(x) {
+                        let cell = cave.index(rx, ry);
+                        cave.cells[cell] = Cell::Rock;
+                    }
+                }
+            }
         }
-
-        cave.insert((sand.x, sand.y));
-        return true;
+        Ok(cave)
     }
-    false
-}
 
-fn simulate_sand2(max_y: usize, cave: &mut HashSet<(usize, usize)>) -> Sand {
-    println!("max_y: {}", max_y);
-    let mut sand = Sand::new(500, 0);
+    fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x - self.x0
+    }
 
-    if cave.contains(&(sand.x, sand.y)) {
-        return sand;
+    fn is_air(&self, x: usize, y: usize) -> bool {
+        self.cells[self.index(x, y)] == Cell::Air
     }
 
-    while sand.y <= max_y {
-        // check down (0, +1)
-        if !cave.contains(&(sand.x, sand.y + 1)) {
-            sand.y += 1;
-            continue;
-        }
+    /// Pour sand until it either falls into the abyss or, with a `floor`, blocks the source.
+    /// Returns how many grains came to rest.
+    fn fill(&mut self, floor: bool, verbose: bool) -> usize {
+        let mut count = 0;
+
+        // Every grain follows the previous one until the spot where that one came to rest, so
+        // it resumes from the top of the path rather than from the source.
+        while let Some(&(x, y)) = self.path.last() {
+            if !floor && y >= self.max_y {
+                // Below the deepest rock there's nothing left to land on
+                break;
+            }
 
-        // check left (-1, +1)
-        if !cave.contains(&(sand.x - 1, sand.y + 1)) {
-            sand.x -= 1;
-            sand.y += 1;
-            continue;
-        }
-        // check right (+1, +1)
-        if !cave.contains(&(sand.x + 1, sand.y + 1)) {
-            sand.x += 1;
-            sand.y += 1;
-            continue;
-        }
-        break;
-    }
-    sand
-}
+            let below = y + 1;
+            if below <= self.max_y + 1 {
+                if let Some(next) = [x, x - 1, x + 1]
+                    .into_iter()
+                    .find(|&nx| self.is_air(nx, below))
+                {
+                    self.path.push((next, below));
+                    continue;
+                }
+            }
 
-fn add_rocks(cave: &mut HashSet<(usize, usize)>, start: (usize, usize), end: (usize, usize)) {
-    if start.0 != end.0 {
-        // draw horizontal wall
-        for x in std::cmp::min(start.0, end.0)..=std::cmp::max(start.0, end.0) {
-            cave.insert((x, start.1));
-        }
-    } else if start.1 != end.1 {
-        // draw vertical wall
-        for y in std::cmp::min(start.1, end.1)..=std::cmp::max(start.1, end.1) {
-            cave.insert((start.0, y));
+            let cell = self.index(x, y);
+            self.cells[cell] = Cell::Sand;
+            self.path.pop();
+            count += 1;
+            if verbose {
+                eprintln!("grain {} rests at {},{}", count, x, y);
+            }
         }
+        count
     }
-}
 
-fn draw_cave(max_y: usize, cave: &HashSet<(usize, usize)>) {
-    for y in 0..=max_y {
-        for x in 0..=600 {
-            if cave.contains(&(x, y)) {
-                print!("#");
-            } else {
-                print!(".");
+    /// The cave cropped to the columns with rock or sand in them, drawn like the puzzle
+    /// description.
+    fn draw(&self) -> String {
+        let used = |x: usize| (0..=self.max_y + 1).any(|y| !self.is_air(x, y));
+        let columns = self.x0..self.x0 + self.width;
+        let (Some(left), Some(right)) = (
+            columns.clone().find(|&x| used(x)),
+            columns.rev().find(|&x| used(x)),
+        ) else {
+            return String::new();
+        };
+        let bottom = (0..=self.max_y + 1)
+            .rev()
+            .find(|&y| (left..=right).any(|x| !self.is_air(x, y)))
+            .unwrap();
+
+        let mut out = String::new();
+        for y in 0..=bottom {
+            for x in left..=right {
+                out.push(match self.cells[self.index(x, y)] {
+                    Cell::Rock => '#',
+                    Cell::Sand => 'o',
+                    Cell::Air if (x, y) == SOURCE => '+',
+                    Cell::Air => '.',
+                });
             }
+            out.push('\n');
         }
-        println!();
+        out
     }
 }
 
 #[allow(dead_code)]
 const TEST_INPUT: &str = r#"498,4 -> 498,6 -> 496,6
 503,4 -> 502,4 -> 502,9 -> 494,9"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parts() {
+        let mut cave = Cave::new(&parse_paths(TEST_INPUT).unwrap()).unwrap();
+        assert_eq!(cave.fill(false, false), 24);
+        assert_eq!(
+            cave.draw(),
+            r#"......+...
+..........
+......o...
+.....ooo..
+....#ooo##
+...o#ooo#.
+..###ooo#.
+....oooo#.
+.o.ooooo#.
+#########.
+"#
+        );
+        assert_eq!(24 + cave.fill(true, false), 93);
+        assert!(cave.path.is_empty());
+    }
+
+    #[test]
+    fn test_part2_from_scratch() {
+        let mut cave = Cave::new(&parse_paths(TEST_INPUT).unwrap()).unwrap();
+        assert_eq!(cave.fill(true, false), 93);
+    }
+
+    #[test]
+    fn test_invalid_rocks() {
+        assert!(parse_paths("498,4 -> 498").is_err());
+        assert!(Cave::new(&parse_paths("498,4 -> 499,5").unwrap()).is_err());
+        assert!(Cave::new(&[]).is_err());
+    }
+}
@@ -1,131 +1,411 @@
-use std::collections::HashSet;
+use std::collections::BTreeSet;
+use std::str::FromStr;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
+
+use geometry::{Bounds, Rect};
+
+const PART1_ROW: i64 = 2_000_000;
+const PART2_MAX: i64 = 4_000_000;
+const TUNING_MULTIPLIER: i64 = 4_000_000;
+// Uncovered cells printed when there is more than one
+const MAX_LISTED: usize = 20;
 
 fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let use_lines = args.first().is_some_and(|arg| arg == "--lines");
+    if use_lines {
+        args.remove(0);
+    }
+    let (row, bounds) = match args.as_slice() {
+        [] => (PART1_ROW, Bounds::square(PART2_MAX)),
+        [row, x_min, x_max, y_min, y_max] => (
+            row.parse()?,
+            Bounds {
+                x_min: x_min.parse()?,
+                x_max: x_max.parse()?,
+                y_min: y_min.parse()?,
+                y_max: y_max.parse()?,
+            },
+        ),
+        _ => bail!("usage: day15 [--lines] [<row> <x min> <x max> <y min> <y max>]"),
+    };
+
     let input = std::fs::read_to_string("./inputs/day15.txt")?;
+    let sensors = parse_sensors(&input)?;
 
-    println!("Part 1: {}", part1(&input, 2_000_000));
-    println!("Part 2: {}", part2(&input, 4_000_000));
+    println!("Part 1: {}", part1(&sensors, row));
+
+    let (count, uncovered) = if use_lines {
+        let cells = uncovered_by_lines(&sensors, &bounds);
+        (cells.len() as u64, cells)
+    } else {
+        let count = uncovered_regions(&sensors, &bounds)
+            .iter()
+            .map(|rect| rect.count(&bounds))
+            .sum();
+        (
+            count,
+            uncovered_by_subtraction(&sensors, &bounds, MAX_LISTED),
+        )
+    };
+    match uncovered.as_slice() {
+        [cell] if count == 1 => println!("Part 2: {}", tuning_frequency(*cell)),
+        cells => {
+            println!("Part 2: {} uncovered cells", count);
+            for &cell in cells.iter() {
+                println!("  {:?} {}", cell, tuning_frequency(cell));
+            }
+            if count > cells.len() as u64 {
+                println!("  ...");
+            }
+        }
+    }
 
     Ok(())
 }
 
-fn part1(input: &String, target_y: i32) -> usize {
-    let sensors = input
+#[derive(Debug)]
+struct Sensor {
+    pos: (i64, i64),
+    beacon: (i64, i64),
+    range: i64,
+}
+
+impl Sensor {
+    fn coverage(&self) -> Rect {
+        Rect::diamond(self.pos, self.range)
+    }
+}
+
+impl FromStr for Sensor {
+    type Err = anyhow::Error;
+
+    // Sensor at x=2, y=18: closest beacon is at x=-2, y=15
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let point = |part: &str| -> Result<(i64, i64)> {
+            let (x, y) = part
+                .split_once("x=")
+                .and_then(|(_, coords)| coords.split_once(", y="))
+                .ok_or_else(|| anyhow!("missing coordinates in {:?}", part))?;
+            Ok((x.parse()?, y.parse()?))
+        };
+
+        let (sensor, beacon) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("missing ':' in {:?}", s))?;
+        let (pos, beacon) = (point(sensor)?, point(beacon)?);
+
+        Ok(Sensor {
+            pos,
+            beacon,
+            range: distance(pos, beacon),
+        })
+    }
+}
+
+fn parse_sensors(input: &str) -> Result<Vec<Sensor>> {
+    input
         .lines()
-        .flat_map(|line| {
-            line.split_once(":").map(|(sensor, beacon)| {
-                let sensor_cords = sensor
-                    .split_once("x=")
-                    .unwrap()
-                    .1
-                    .split_once(", y=")
-                    .unwrap();
-                let sensor_x = sensor_cords.0.parse::<i32>().unwrap();
-                let sensor_y = sensor_cords.1.parse::<i32>().unwrap();
-
-                let beacon_cords = beacon
-                    .split_once("x=")
-                    .unwrap()
-                    .1
-                    .split_once(", y=")
-                    .unwrap();
-                let beacon_x = beacon_cords.0.parse::<i32>().unwrap();
-                let beacon_y = beacon_cords.1.parse::<i32>().unwrap();
-                ((sensor_x, sensor_y), (beacon_x, beacon_y))
-            })
+        .enumerate()
+        .map(|(i, line)| {
+            line.parse::<Sensor>()
+                .with_context(|| format!("invalid sensor on line {}", i + 1))
+        })
+        .collect()
+}
+
+//  Taxicab Distance: | x 1 − x 2 | + | y 1 − y 2 |
+fn distance(a: (i64, i64), b: (i64, i64)) -> i64 {
+    (a.0 - b.0).abs() + (a.1 - b.1).abs()
+}
+
+fn tuning_frequency((x, y): (i64, i64)) -> i64 {
+    TUNING_MULTIPLIER * x + y
+}
+
+fn is_covered(sensors: &[Sensor], cell: (i64, i64)) -> bool {
+    sensors
+        .iter()
+        .any(|sensor| distance(sensor.pos, cell) <= sensor.range)
+}
+
+/// Number of cells on `row` where a beacon can't be.
+fn part1(sensors: &[Sensor], row: i64) -> usize {
+    // Each sensor covers one contiguous run of the row, so merge the runs and add them up
+    let mut runs = sensors
+        .iter()
+        .filter_map(|sensor| {
+            let reach = sensor.range - (sensor.pos.1 - row).abs();
+            (reach >= 0).then_some((sensor.pos.0 - reach, sensor.pos.0 + reach))
         })
         .collect::<Vec<_>>();
+    runs.sort();
 
-    let mut no_beacons = HashSet::<(i32, i32)>::new();
-    let mut yes_beacons = HashSet::<(i32, i32)>::new();
-    for (sensor, beacon) in sensors.iter() {
-        let d = distance(sensor, beacon);
-        let y_dist = (sensor.1 - target_y).abs();
-        let x_dist = d - y_dist;
-        for x in sensor.0 - x_dist..=sensor.0 + x_dist {
-            no_beacons.insert((x, target_y));
-        }
-        if beacon.1 == target_y {
-            yes_beacons.insert(*beacon);
+    let mut covered = 0;
+    let mut end = i64::MIN;
+    for (lo, hi) in runs {
+        let lo = lo.max(end + 1);
+        if lo <= hi {
+            covered += (hi - lo + 1) as usize;
+            end = hi;
         }
     }
 
-    for yes_beacon in yes_beacons.iter() {
-        no_beacons.remove(yes_beacon);
+    let beacons = sensors
+        .iter()
+        .filter(|sensor| sensor.beacon.1 == row)
+        .map(|sensor| sensor.beacon)
+        .collect::<BTreeSet<_>>();
+    covered - beacons.len()
+}
+
+// Reference for `part1`, checking every cell any sensor could reach one at a time
+#[cfg(test)]
+fn part1_by_scan(sensors: &[Sensor], row: i64) -> usize {
+    let x_min = sensors.iter().map(|s| s.pos.0 - s.range).min().unwrap_or(0);
+    let x_max = sensors
+        .iter()
+        .map(|s| s.pos.0 + s.range)
+        .max()
+        .unwrap_or(-1);
+    (x_min..=x_max)
+        .filter(|&x| is_covered(sensors, (x, row)) && sensors.iter().all(|s| s.beacon != (x, row)))
+        .count()
+}
+
+// Reference for the part 2 searches, checking every cell in `bounds` one at a time
+#[cfg(test)]
+fn uncovered_by_scan(sensors: &[Sensor], bounds: &Bounds) -> Vec<(i64, i64)> {
+    let mut cells = vec![];
+    for x in bounds.x_min..=bounds.x_max {
+        for y in bounds.y_min..=bounds.y_max {
+            if !is_covered(sensors, (x, y)) {
+                cells.push((x, y));
+            }
+        }
     }
+    cells
+}
 
-    no_beacons.len()
+/// The parts of `bounds` that no sensor covers, found by cutting each sensor's coverage out of
+/// the search area. This is exhaustive, so it finds uncovered regions of any shape, and stays
+/// small however many cells the regions hold.
+fn uncovered_regions(sensors: &[Sensor], bounds: &Bounds) -> Vec<Rect> {
+    let mut remaining = vec![bounds.rotated()];
+    for sensor in sensors {
+        let coverage = sensor.coverage();
+        remaining = remaining
+            .iter()
+            .flat_map(|rect| rect.subtract(&coverage))
+            .collect();
+    }
+    remaining
 }
 
-fn part2(input: &String, search_space: i32) -> usize {
-    let sensors = input
-        .lines()
-        .flat_map(|line| {
-            line.split_once(":").map(|(sensor, beacon)| {
-                let sensor_cords = sensor
-                    .split_once("x=")
-                    .unwrap()
-                    .1
-                    .split_once(", y=")
-                    .unwrap();
-                let sensor_x = sensor_cords.0.parse::<i32>().unwrap();
-                let sensor_y = sensor_cords.1.parse::<i32>().unwrap();
-
-                let beacon_cords = beacon
-                    .split_once("x=")
-                    .unwrap()
-                    .1
-                    .split_once(", y=")
-                    .unwrap();
-                let beacon_x = beacon_cords.0.parse::<i32>().unwrap();
-                let beacon_y = beacon_cords.1.parse::<i32>().unwrap();
-                let d = distance(&(sensor_x, sensor_y), &(beacon_x, beacon_y));
-                ((sensor_x, sensor_y), d)
-            })
-        })
+/// Up to `limit` uncovered cells in `bounds`, sorted. All of them if there are no more than
+/// that, otherwise whichever the regions list first.
+fn uncovered_by_subtraction(sensors: &[Sensor], bounds: &Bounds, limit: usize) -> Vec<(i64, i64)> {
+    let mut cells = uncovered_regions(sensors, bounds)
+        .iter()
+        .flat_map(|rect| rect.cells(bounds))
+        .take(limit)
         .collect::<Vec<_>>();
+    cells.sort();
+    cells
+}
+
+/// Uncovered cells in `bounds` found by intersecting the lines just outside each sensor's
+/// coverage. An isolated uncovered cell is always hemmed in by those lines, but a larger
+/// uncovered region is only reported where its edges cross.
+fn uncovered_by_lines(sensors: &[Sensor], bounds: &Bounds) -> Vec<(i64, i64)> {
+    let mut u_lines = BTreeSet::new();
+    let mut v_lines = BTreeSet::new();
+    for sensor in sensors {
+        let rect = sensor.coverage();
+        u_lines.extend([rect.u0 - 1, rect.u1 + 1]);
+        v_lines.extend([rect.v0 - 1, rect.v1 + 1]);
+    }
 
-    let answer = find_tuning_freq(&sensors, search_space);
+    // The corners of the search area aren't on any sensor's lines
+    let corners = [
+        (bounds.x_min, bounds.y_min),
+        (bounds.x_min, bounds.y_max),
+        (bounds.x_max, bounds.y_min),
+        (bounds.x_max, bounds.y_max),
+    ];
+    let crossings = u_lines.iter().flat_map(|&u| {
+        v_lines
+            .iter()
+            .filter_map(move |&v| geometry::unrotate(u, v))
+    });
 
-    answer as usize
+    crossings
+        .chain(corners)
+        .filter(|&cell| bounds.contains(cell) && !is_covered(sensors, cell))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
 }
 
-//  Taxicab Distance: | x 1 − x 2 | + | y 1 − y 2 |
-fn distance(sensor: &(i32, i32), beacon: &(i32, i32)) -> i32 {
-    (sensor.0 - beacon.0).abs() + (sensor.1 - beacon.1).abs()
-}
-
-fn find_tuning_freq(sensors: &Vec<((i32, i32), i32)>, search_space: i32) -> u64 {
-    for (sensor, d) in sensors.iter() {
-        for dx in 0..=d + 1 {
-            let dy = d + 1 - dx;
-            for (x, y) in [
-                (sensor.0 + dx, sensor.1 + dy),
-                (sensor.0 - dx, sensor.1 - dy),
-                (sensor.0 - dx, sensor.1 + dy),
-                (sensor.0 + dx, sensor.1 - dy),
-            ] {
-                if x < 0 || y < 0 || x > search_space || y > search_space {
-                    continue;
-                }
-                if check_point(x, y, sensors) {
-                    return 4_000_000 * x as u64 + y as u64;
-                }
+/*
+Turning the grid 45 degrees with u = x + y and v = x - y makes every sensor's diamond of coverage
+an axis-aligned square, |u - uc| <= r and |v - vc| <= r. Not every (u, v) is a cell though: only
+those where u and v have the same parity map back to integer x and y.
+*/
+mod geometry {
+    /// A rectangle in rotated coordinates, inclusive at both ends.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Rect {
+        pub u0: i64,
+        pub u1: i64,
+        pub v0: i64,
+        pub v1: i64,
+    }
+
+    /// An axis-aligned search area in the original coordinates, inclusive at both ends.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Bounds {
+        pub x_min: i64,
+        pub x_max: i64,
+        pub y_min: i64,
+        pub y_max: i64,
+    }
+
+    pub fn rotate((x, y): (i64, i64)) -> (i64, i64) {
+        (x + y, x - y)
+    }
+
+    pub fn unrotate(u: i64, v: i64) -> Option<(i64, i64)> {
+        ((u - v) % 2 == 0).then_some(((u + v) / 2, (u - v) / 2))
+    }
+
+    impl Rect {
+        pub fn diamond(center: (i64, i64), radius: i64) -> Self {
+            let (u, v) = rotate(center);
+            Rect {
+                u0: u - radius,
+                u1: u + radius,
+                v0: v - radius,
+                v1: v + radius,
+            }
+        }
+
+        fn overlaps(&self, other: &Rect) -> bool {
+            self.u0 <= other.u1 && other.u0 <= self.u1 && self.v0 <= other.v1 && other.v0 <= self.v1
+        }
+
+        /// The parts of `self` outside `other`, as up to four disjoint rectangles.
+        pub fn subtract(&self, other: &Rect) -> Vec<Rect> {
+            if !self.overlaps(other) {
+                return vec![*self];
             }
+
+            let (mid_u0, mid_u1) = (self.u0.max(other.u0), self.u1.min(other.u1));
+            [
+                // Left and right of other, full height
+                Rect {
+                    u1: other.u0 - 1,
+                    ..*self
+                },
+                Rect {
+                    u0: other.u1 + 1,
+                    ..*self
+                },
+                // Below and above other, only across the overlapping columns
+                Rect {
+                    u0: mid_u0,
+                    u1: mid_u1,
+                    v1: other.v0 - 1,
+                    ..*self
+                },
+                Rect {
+                    u0: mid_u0,
+                    u1: mid_u1,
+                    v0: other.v1 + 1,
+                    ..*self
+                },
+            ]
+            .into_iter()
+            .filter(|rect| rect.u0 <= rect.u1 && rect.v0 <= rect.v1)
+            .collect()
+        }
+
+        /// Every cell in both this rectangle and `bounds`.
+        // The u values with any cell inside `bounds`, each with the first v of the right parity
+        // and the last v allowed
+        fn columns(&self, bounds: &Bounds) -> impl Iterator<Item = (i64, i64, i64)> + '_ {
+            let Bounds {
+                x_min,
+                x_max,
+                y_min,
+                y_max,
+            } = *bounds;
+
+            // For a given u the bounds limit v to [2 x_min - u, 2 x_max - u] and
+            // [u - 2 y_max, u - 2 y_min]. Pairing each lower limit on v with each upper one
+            // gives the u values where any v is left, so columns outside the bounds are skipped
+            // without visiting them.
+            let u_min = self
+                .u0
+                .max(self.v0 + 2 * y_min)
+                .max(2 * x_min - self.v1)
+                .max(x_min + y_min);
+            let u_max = self
+                .u1
+                .min(2 * x_max - self.v0)
+                .min(self.v1 + 2 * y_max)
+                .min(x_max + y_max);
+
+            (u_min..=u_max).map(move |u| {
+                let v_min = self.v0.max(2 * x_min - u).max(u - 2 * y_max);
+                let v_max = self.v1.min(2 * x_max - u).min(u - 2 * y_min);
+                // Step v two at a time from the first one with u's parity
+                (u, v_min + (u - v_min).rem_euclid(2), v_max)
+            })
+        }
+
+        pub fn cells<'a>(&'a self, bounds: &Bounds) -> impl Iterator<Item = (i64, i64)> + 'a {
+            self.columns(bounds).flat_map(|(u, first, v_max)| {
+                (first..=v_max)
+                    .step_by(2)
+                    .filter_map(move |v| unrotate(u, v))
+            })
+        }
+
+        /// Number of cells inside `bounds`, without listing them.
+        pub fn count(&self, bounds: &Bounds) -> u64 {
+            self.columns(bounds)
+                .filter(|(_, first, v_max)| first <= v_max)
+                .map(|(_, first, v_max)| ((v_max - first) / 2 + 1) as u64)
+                .sum()
         }
     }
-    0
-}
 
-fn check_point(x: i32, y: i32, sensors: &Vec<((i32, i32), i32)>) -> bool {
-    for (sensor, d) in sensors.iter() {
-        if distance(&(x, y), sensor) <= *d {
-            return false;
+    impl Bounds {
+        pub fn square(max: i64) -> Self {
+            Bounds {
+                x_min: 0,
+                x_max: max,
+                y_min: 0,
+                y_max: max,
+            }
+        }
+
+        pub fn contains(&self, (x, y): (i64, i64)) -> bool {
+            (self.x_min..=self.x_max).contains(&x) && (self.y_min..=self.y_max).contains(&y)
+        }
+
+        /// The smallest rotated rectangle holding the whole search area.
+        pub fn rotated(&self) -> Rect {
+            Rect {
+                u0: self.x_min + self.y_min,
+                u1: self.x_max + self.y_max,
+                v0: self.x_min - self.y_max,
+                v1: self.x_max - self.y_min,
+            }
         }
     }
-    true
 }
 
 #[allow(dead_code)]
@@ -143,3 +423,138 @@ Sensor at x=17, y=20: closest beacon is at x=21, y=22
 Sensor at x=16, y=7: closest beacon is at x=15, y=3
 Sensor at x=14, y=3: closest beacon is at x=15, y=3
 Sensor at x=20, y=1: closest beacon is at x=15, y=3"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aoc2022::generate;
+    use proptest::prelude::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_part1() {
+        let sensors = parse_sensors(TEST_INPUT).unwrap();
+        assert_eq!(part1(&sensors, 10), 26);
+    }
+
+    #[test]
+    fn test_part2() {
+        let sensors = parse_sensors(TEST_INPUT).unwrap();
+        let bounds = Bounds::square(20);
+        assert_eq!(
+            uncovered_by_subtraction(&sensors, &bounds, usize::MAX),
+            vec![(14, 11)]
+        );
+        assert_eq!(uncovered_by_lines(&sensors, &bounds), vec![(14, 11)]);
+        assert_eq!(tuning_frequency((14, 11)), 56000011);
+    }
+
+    #[test]
+    fn test_subtraction_matches_brute_force() {
+        let sensors = parse_sensors(TEST_INPUT).unwrap();
+        for bounds in [
+            Bounds::square(25),
+            Bounds {
+                x_min: -8,
+                x_max: 30,
+                y_min: -3,
+                y_max: 27,
+            },
+        ] {
+            let expected = uncovered_by_scan(&sensors, &bounds);
+            assert_eq!(
+                uncovered_by_subtraction(&sensors, &bounds, usize::MAX),
+                expected
+            );
+
+            // The line crossings only ever find genuinely uncovered cells
+            let crossings = uncovered_by_lines(&sensors, &bounds);
+            assert!(crossings.iter().all(|cell| expected.contains(cell)));
+        }
+    }
+
+    #[test]
+    fn test_large_uncovered_area() {
+        // Nothing covers the puzzle's search area, which is far too many cells to list
+        let bounds = Bounds::square(PART2_MAX);
+        let regions = uncovered_regions(&[], &bounds);
+        let count = regions.iter().map(|rect| rect.count(&bounds)).sum::<u64>();
+        assert_eq!(count, (PART2_MAX as u64 + 1).pow(2));
+        assert_eq!(uncovered_by_subtraction(&[], &bounds, 5).len(), 5);
+    }
+
+    #[test]
+    fn test_rect_subtract() {
+        let a = Rect::diamond((0, 0), 3);
+        let b = Rect::diamond((1, 1), 1);
+        let pieces = a.subtract(&b);
+        let area = |r: &Rect| (r.u1 - r.u0 + 1) * (r.v1 - r.v0 + 1);
+        assert_eq!(pieces.iter().map(area).sum::<i64>(), area(&a) - area(&b));
+        assert_eq!(a.subtract(&Rect::diamond((20, 0), 1)), vec![a]);
+        assert!(a.subtract(&a).is_empty());
+    }
+
+    fn sensor() -> impl Strategy<Value = Sensor> {
+        (-20..=40i64, -20..=40i64, -6..=6i64, -6..=6i64).prop_map(|(x, y, dx, dy)| {
+            let (pos, beacon) = ((x, y), (x + dx, y + dy));
+            Sensor {
+                pos,
+                beacon,
+                range: distance(pos, beacon),
+            }
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn generated_input_has_one_gap(
+            seed in any::<u64>(),
+            count in 4..=12usize,
+            max in 4..=40i64,
+        ) {
+            let input = generate::day15(&mut StdRng::seed_from_u64(seed), count, max);
+            let sensors = parse_sensors(&input).unwrap();
+            let bounds = Bounds::square(max);
+
+            let expected = uncovered_by_scan(&sensors, &bounds);
+            prop_assert_eq!(expected.len(), 1);
+            prop_assert_eq!(&uncovered_by_subtraction(&sensors, &bounds, usize::MAX), &expected);
+            prop_assert_eq!(&uncovered_by_lines(&sensors, &bounds), &expected);
+        }
+
+        #[test]
+        fn subtraction_matches_scan(
+            sensors in prop::collection::vec(sensor(), 0..8),
+            x_min in -10..=10i64,
+            y_min in -10..=10i64,
+            width in 0..=30i64,
+            height in 0..=30i64,
+        ) {
+            let bounds = Bounds {
+                x_min,
+                x_max: x_min + width,
+                y_min,
+                y_max: y_min + height,
+            };
+            let expected = uncovered_by_scan(&sensors, &bounds);
+            prop_assert_eq!(uncovered_by_subtraction(&sensors, &bounds, usize::MAX), expected.clone());
+            let count = uncovered_regions(&sensors, &bounds)
+                .iter()
+                .map(|rect| rect.count(&bounds))
+                .sum::<u64>();
+            prop_assert_eq!(count, expected.len() as u64);
+            let limited = uncovered_by_subtraction(&sensors, &bounds, 3);
+            prop_assert_eq!(limited.len(), expected.len().min(3));
+            prop_assert!(limited.iter().all(|cell| expected.contains(cell)));
+
+            // Lines only promise cells that really are uncovered
+            let crossings = uncovered_by_lines(&sensors, &bounds);
+            prop_assert!(crossings.iter().all(|cell| expected.contains(cell)));
+        }
+
+        #[test]
+        fn part1_matches_scan(sensors in prop::collection::vec(sensor(), 0..8), row in -30..=50i64) {
+            prop_assert_eq!(part1(&sensors, row), part1_by_scan(&sensors, row));
+        }
+    }
+}
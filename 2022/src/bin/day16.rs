@@ -1,6 +1,15 @@
 use std::collections::HashMap;
+use std::str::FromStr;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
+
+const START: &str = "AA";
+
+// Per-agent results are kept for every subset of useful valves, so this bounds memory use
+const MAX_USEFUL_VALVES: usize = 20;
+
+// Large enough to mean "unreachable" without overflowing when two distances are added
+const UNREACHABLE: u32 = u32::MAX / 4;
 
 #[derive(Debug, Default)]
 struct Valve {
@@ -9,149 +18,380 @@ struct Valve {
     neighbors: Vec<String>,
 }
 
-fn main() -> Result<()> {
-    let input = std::fs::read_to_string("./inputs/day16.txt")?;
+impl FromStr for Valve {
+    type Err = anyhow::Error;
 
-    println!("Part 1: {}", part(&input.to_string(), 30, false));
-    println!("Part 2: {}", part(&input.to_string(), 26, true));
+    // Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (valve, tunnels) = s
+            .split_once("; ")
+            .ok_or_else(|| anyhow!("missing ';' in {:?}", s))?;
 
-    Ok(())
-}
+        let label = valve
+            .strip_prefix("Valve ")
+            .and_then(|rest| rest.split_whitespace().next())
+            .ok_or_else(|| anyhow!("missing valve label in {:?}", s))?
+            .to_string();
+        let rate = valve
+            .split_once("rate=")
+            .ok_or_else(|| anyhow!("missing flow rate in {:?}", s))?
+            .1
+            .parse::<u32>()
+            .with_context(|| format!("invalid flow rate in {:?}", s))?;
+        let neighbors = tunnels
+            .split_whitespace()
+            .skip(4)
+            .map(|neighbor| neighbor.trim_end_matches(',').to_string())
+            .collect::<Vec<_>>();
 
-fn part(input: &String, time: u32, elephant: bool) -> u32 {
-    let valves = input
-        .lines()
-        .map(|line| {
-            let mut parts = line.split(" ");
-            let label = parts.nth(1).unwrap().to_string();
-            let rate = parts
-                .nth(2)
-                .unwrap()
-                .split_once(";")
-                .unwrap()
-                .0
-                .split_once("=")
-                .unwrap()
-                .1
-                .parse::<u32>()
-                .unwrap();
-
-            let neighbors = parts
-                .skip(4)
-                .map(|s| s.to_string().replace(",", ""))
-                .collect::<Vec<_>>();
-
-            (
-                label.clone(),
-                Valve {
-                    label,
-                    rate,
-                    neighbors,
-                },
-            )
+        Ok(Valve {
+            label,
+            rate,
+            neighbors,
         })
-        .collect::<HashMap<String, Valve>>();
-
-    // Create an hashmap of valve indices for only the valves that have a non-zero flow rate.
-    // These indices are used for tracking which valves are open via a bitmask.
-    let bitmask_index = valves
-        .values()
-        .filter_map(|v| {
-            if v.label != "AA" && v.rate == 0 {
-                None
-            } else {
-                Some(v.label.clone())
+    }
+}
+
+// The order valves are opened in, as (valve index, minute it was opened) pairs
+#[derive(Debug, Default, Clone)]
+struct Plan {
+    pressure: u32,
+    opened: Vec<(usize, u32)>,
+}
+
+/*
+Zero-rate valves are only ever walked through, so the tunnels are collapsed down to the valves
+worth opening (plus the start) with the walking time between every pair of them. Each agent's
+route is then just an order to open valves in, and each opened set is tracked as a bitmask.
+*/
+#[derive(Debug)]
+struct Network {
+    labels: Vec<String>,
+    rates: Vec<u32>,
+    // Minutes to walk between useful valves, with the start valve as the last index
+    dist: Vec<Vec<u32>>,
+}
+
+impl Network {
+    fn new(valves: &[Valve]) -> Result<Self> {
+        let index = valves
+            .iter()
+            .enumerate()
+            .map(|(i, valve)| (valve.label.as_str(), i))
+            .collect::<HashMap<_, _>>();
+
+        // Floyd-Warshall over the full tunnel graph
+        let n = valves.len();
+        let mut dist = vec![vec![UNREACHABLE; n]; n];
+        for (i, valve) in valves.iter().enumerate() {
+            dist[i][i] = 0;
+            for neighbor in valve.neighbors.iter() {
+                let j = *index.get(neighbor.as_str()).ok_or_else(|| {
+                    anyhow!("valve {} leads to unknown valve {}", valve.label, neighbor)
+                })?;
+                dist[i][j] = 1;
             }
+        }
+        for k in 0..n {
+            for i in 0..n {
+                for j in 0..n {
+                    dist[i][j] = dist[i][j].min(dist[i][k] + dist[k][j]);
+                }
+            }
+        }
+
+        let start = *index
+            .get(START)
+            .ok_or_else(|| anyhow!("no start valve {}", START))?;
+        let mut keep = (0..n).filter(|&i| valves[i].rate > 0).collect::<Vec<_>>();
+        if keep.len() > MAX_USEFUL_VALVES {
+            bail!(
+                "{} valves have a flow rate, at most {} are supported",
+                keep.len(),
+                MAX_USEFUL_VALVES
+            );
+        }
+
+        let labels = keep.iter().map(|&i| valves[i].label.clone()).collect();
+        let rates = keep.iter().map(|&i| valves[i].rate).collect();
+        keep.push(start);
+        let dist = keep
+            .iter()
+            .map(|&i| keep.iter().map(|&j| dist[i][j]).collect())
+            .collect();
+
+        Ok(Network {
+            labels,
+            rates,
+            dist,
         })
-        .collect::<Vec<_>>()
-        .into_iter()
-        .enumerate()
-        .map(|(i, v)| (v, i as u32))
-        .collect::<HashMap<String, u32>>();
-
-    println!("Mask index: {:?}", bitmask_index);
-
-    let opened_bitmask = 0;
-    let mut cache = HashMap::<(String, u32, u32, bool), u32>::new();
-
-    solve(
-        valves.get("AA").unwrap(),
-        time,
-        opened_bitmask,
-        &mut cache,
-        &valves,
-        &bitmask_index,
-        elephant,
-    )
-}
+    }
+
+    // Best single-agent plan for every set of valves that can be opened in time, indexed by the
+    // exact set opened
+    fn single_agent(&self, minutes: u32) -> Vec<Option<Plan>> {
+        let mut best = vec![None; 1 << self.rates.len()];
+        let mut path = vec![];
+        self.explore(self.rates.len(), minutes, 0, 0, &mut path, &mut best);
+
+        // Paths record the time left when each valve opened; turn that into minutes elapsed
+        for plan in best.iter_mut().flatten() {
+            for (_, minute) in plan.opened.iter_mut() {
+                *minute = minutes - *minute;
+            }
+        }
+        best
+    }
 
-fn solve(
-    valve: &Valve,
-    time: u32,
-    opened_bitmask: u32,
-    cache: &mut HashMap<(String, u32, u32, bool), u32>,
-    valves: &HashMap<String, Valve>,
-    bitmask_index: &HashMap<String, u32>,
-    elephant: bool,
-) -> u32 {
-    if time == 0 {
-        if elephant {
-            return solve(
-                valves.get("AA").unwrap(),
-                26,
-                opened_bitmask,
-                cache,
-                valves,
-                bitmask_index,
-                false,
+    fn explore(
+        &self,
+        at: usize,
+        time_left: u32,
+        opened: usize,
+        pressure: u32,
+        path: &mut Vec<(usize, u32)>,
+        best: &mut [Option<Plan>],
+    ) {
+        if best[opened]
+            .as_ref()
+            .is_none_or(|plan| pressure > plan.pressure)
+        {
+            best[opened] = Some(Plan {
+                pressure,
+                opened: path.clone(),
+            });
+        }
+
+        for next in 0..self.rates.len() {
+            // Walking there and opening it has to leave at least a minute of flow
+            let cost = self.dist[at][next] + 1;
+            if opened & (1 << next) != 0 || cost >= time_left {
+                continue;
+            }
+
+            let left = time_left - cost;
+            path.push((next, left));
+            self.explore(
+                next,
+                left,
+                opened | (1 << next),
+                pressure + self.rates[next] * left,
+                path,
+                best,
             );
+            path.pop();
+        }
+    }
+
+    /// Best plan for each of `agents` agents working at the same time for `minutes`.
+    fn solve(&self, agents: usize, minutes: u32) -> Vec<Plan> {
+        if agents == 0 {
+            return vec![];
+        }
+
+        let full = (1 << self.rates.len()) - 1;
+        let single = self.single_agent(minutes);
+        let pressure = |set: usize| single[set].as_ref().map(|plan| plan.pressure);
+
+        // best[m] is the most one agent can release using only valves in m, by opening choice[m]
+        let mut best = vec![0u32; full + 1];
+        let mut choice = vec![0usize; full + 1];
+        for m in 0..=full {
+            if let Some(p) = pressure(m) {
+                best[m] = p;
+                choice[m] = m;
+            }
+            for bit in 0..self.rates.len() {
+                let sub = m & !(1 << bit);
+                if sub != m && best[sub] > best[m] {
+                    best[m] = best[sub];
+                    choice[m] = choice[sub];
+                }
+            }
+        }
+
+        // Each extra agent takes a set of valves and leaves the rest to the agents before it.
+        // Only the full set is needed for the last agent.
+        let mut choices = vec![choice];
+        for k in 2..=agents {
+            let mut next_best = vec![0u32; full + 1];
+            let mut next_choice = vec![0usize; full + 1];
+            let masks = if k == agents { full..=full } else { 0..=full };
+
+            for m in masks {
+                // Walk every subset of m
+                let mut s = m;
+                loop {
+                    if let Some(p) = pressure(s) {
+                        let total = p + best[m ^ s];
+                        if total > next_best[m] {
+                            next_best[m] = total;
+                            next_choice[m] = s;
+                        }
+                    }
+                    if s == 0 {
+                        break;
+                    }
+                    s = (s - 1) & m;
+                }
+            }
+
+            best = next_best;
+            choices.push(next_choice);
+        }
+
+        let mut remaining = full;
+        let mut plans = vec![];
+        for choice in choices.iter().rev() {
+            let set = choice[remaining];
+            plans.push(single[set].clone().unwrap_or_default());
+            remaining ^= set;
         }
-        return 0;
+        plans
     }
 
-    if let Some(&cached) = cache.get(&(valve.label.clone(), time, opened_bitmask, elephant)) {
-        return cached;
+    fn describe(&self, plan: &Plan) -> String {
+        plan.opened
+            .iter()
+            .map(|&(valve, minute)| format!("{}@{}", self.labels[valve], minute))
+            .collect::<Vec<_>>()
+            .join(" ")
     }
+}
+
+/*
+Reference for `Network::solve` that follows the puzzle literally: every minute each agent opens
+the valve it is standing at, walks down a tunnel or waits. Nothing is collapsed or cached per
+valve set, only whole states are memoised so small networks finish in reasonable time.
+*/
+#[cfg(test)]
+fn max_pressure_naive(valves: &[Valve], agents: usize, minutes: u32) -> Result<u32> {
+    type State = (Vec<usize>, u64, u32);
 
-    let scores = valve
-        .neighbors
+    fn best(
+        valves: &[Valve],
+        tunnels: &[Vec<usize>],
+        state: State,
+        memo: &mut HashMap<State, u32>,
+    ) -> u32 {
+        let (positions, opened, time_left) = state;
+        if time_left == 0 {
+            return 0;
+        }
+        if let Some(&pressure) = memo.get(&(positions.clone(), opened, time_left)) {
+            return pressure;
+        }
+
+        // Every combination of moves, one agent at a time
+        let mut moves = vec![(vec![], opened, 0)];
+        for &at in positions.iter() {
+            let mut next = vec![];
+            for (moved, opened, gained) in moves {
+                if valves[at].rate > 0 && opened & (1 << at) == 0 {
+                    let gain = valves[at].rate * (time_left - 1);
+                    next.push((
+                        [moved.clone(), vec![at]].concat(),
+                        opened | 1 << at,
+                        gained + gain,
+                    ));
+                }
+                for &to in tunnels[at].iter().chain([&at]) {
+                    next.push(([moved.clone(), vec![to]].concat(), opened, gained));
+                }
+            }
+            moves = next;
+        }
+
+        let mut pressure = 0;
+        for (mut moved, opened, gained) in moves {
+            // Agents are interchangeable, so their order doesn't matter
+            moved.sort();
+            pressure =
+                pressure.max(gained + best(valves, tunnels, (moved, opened, time_left - 1), memo));
+        }
+        memo.insert((positions, opened, time_left), pressure);
+        pressure
+    }
+
+    if valves.len() > u64::BITS as usize {
+        bail!("at most {} valves are supported", u64::BITS);
+    }
+    let index = valves
+        .iter()
+        .enumerate()
+        .map(|(i, valve)| (valve.label.as_str(), i))
+        .collect::<HashMap<_, _>>();
+    let tunnels = valves
         .iter()
-        .map(|neighbor| {
-            let neighbor_valve = valves.get(neighbor).unwrap();
-            solve(
-                neighbor_valve,
-                time - 1,
-                opened_bitmask,
-                cache,
-                valves,
-                bitmask_index,
-                elephant,
-            )
+        .map(|valve| {
+            valve
+                .neighbors
+                .iter()
+                .map(|neighbor| {
+                    index
+                        .get(neighbor.as_str())
+                        .copied()
+                        .ok_or_else(|| anyhow!("unknown valve {}", neighbor))
+                })
+                .collect::<Result<Vec<_>>>()
         })
-        .collect::<Vec<u32>>();
-
-    let mut score = *scores.iter().max().unwrap();
-
-    let bit = 1 << *bitmask_index.get(&valve.label).unwrap_or(&0);
-
-    if valve.rate > 0 && (opened_bitmask & bit) == 0 {
-        score = score.max(
-            (time - 1) * valve.rate
-                + solve(
-                    valve,
-                    time - 1,
-                    opened_bitmask | bit,
-                    cache,
-                    valves,
-                    bitmask_index,
-                    elephant,
-                ),
+        .collect::<Result<Vec<_>>>()?;
+    let start = *index
+        .get(START)
+        .ok_or_else(|| anyhow!("no start valve {}", START))?;
+
+    let state = (vec![start; agents], 0, minutes);
+    Ok(best(valves, &tunnels, state, &mut HashMap::new()))
+}
+
+fn report(network: &Network, plans: &[Plan]) {
+    for (i, plan) in plans.iter().enumerate() {
+        println!(
+            "  agent {} ({}): {}",
+            i + 1,
+            plan.pressure,
+            network.describe(plan)
         );
     }
+}
+
+fn total(plans: &[Plan]) -> u32 {
+    plans.iter().map(|plan| plan.pressure).sum()
+}
+
+fn main() -> Result<()> {
+    let mut custom = None;
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        match args.as_slice() {
+            [agents, minutes] => custom = Some((agents.parse::<usize>()?, minutes.parse::<u32>()?)),
+            _ => bail!("usage: day16 [<agents> <minutes>]"),
+        }
+    }
+
+    let input = std::fs::read_to_string("./inputs/day16.txt")?;
+    let valves = input
+        .lines()
+        .map(|line| line.parse::<Valve>())
+        .collect::<Result<Vec<_>>>()?;
+    let network = Network::new(&valves)?;
+
+    if let Some((agents, minutes)) = custom {
+        let plans = network.solve(agents, minutes);
+        println!("{} agents, {} minutes: {}", agents, minutes, total(&plans));
+        report(&network, &plans);
+        return Ok(());
+    }
 
-    cache.insert((valve.label.clone(), time, opened_bitmask, elephant), score);
+    let part1 = network.solve(1, 30);
+    println!("Part 1: {}", total(&part1));
+    report(&network, &part1);
 
-    score
+    let part2 = network.solve(2, 26);
+    println!("Part 2: {}", total(&part2));
+    report(&network, &part2);
+
+    Ok(())
 }
 
 #[allow(dead_code)]
@@ -165,3 +405,83 @@ Valve GG has flow rate=0; tunnels lead to valves FF, HH
 Valve HH has flow rate=22; tunnel leads to valve GG
 Valve II has flow rate=0; tunnels lead to valves AA, JJ
 Valve JJ has flow rate=21; tunnel leads to valve II"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aoc2022::generate;
+    use proptest::prelude::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn parse(input: &str) -> Vec<Valve> {
+        input
+            .lines()
+            .map(|line| line.parse::<Valve>().unwrap())
+            .collect()
+    }
+
+    fn network() -> Network {
+        Network::new(&parse(TEST_INPUT)).unwrap()
+    }
+
+    #[test]
+    fn test_part1() {
+        let network = network();
+        let plans = network.solve(1, 30);
+        assert_eq!(total(&plans), 1651);
+        assert_eq!(
+            network.describe(&plans[0]),
+            "DD@2 BB@5 JJ@9 HH@17 EE@21 CC@24"
+        );
+    }
+
+    #[test]
+    fn test_part2() {
+        let network = network();
+        let plans = network.solve(2, 26);
+        assert_eq!(total(&plans), 1707);
+        assert_eq!(plans.len(), 2);
+    }
+
+    #[test]
+    fn test_more_agents() {
+        let network = network();
+        // Every valve is reachable, so with one agent per valve each opens its valve as
+        // early as possible
+        let plans = network.solve(6, 30);
+        let best = network
+            .rates
+            .iter()
+            .enumerate()
+            .map(|(i, rate)| rate * (30 - network.dist[network.rates.len()][i] - 1))
+            .sum::<u32>();
+        assert_eq!(total(&plans), best);
+        assert_eq!(total(&network.solve(1, 0)), 0);
+    }
+
+    #[test]
+    fn test_naive() {
+        let valves = parse(TEST_INPUT);
+        assert_eq!(max_pressure_naive(&valves, 1, 30).unwrap(), 1651);
+        assert_eq!(max_pressure_naive(&valves, 2, 26).unwrap(), 1707);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn solve_matches_naive(
+            seed in any::<u64>(),
+            count in 2..=8usize,
+            agents in 1..=2usize,
+            minutes in 0..=16u32,
+        ) {
+            let valves = parse(&generate::day16(&mut StdRng::seed_from_u64(seed), count));
+            let network = Network::new(&valves).unwrap();
+            prop_assert_eq!(
+                total(&network.solve(agents, minutes)),
+                max_pressure_naive(&valves, agents, minutes).unwrap()
+            );
+        }
+    }
+}
@@ -1,246 +1,394 @@
 use std::collections::HashMap;
 
-use anyhow::Result;
-
-const EMPTY: u8 = 0;
-const FALLING: u8 = 1;
-const STOPPED: u8 = 2;
-
+use anyhow::{anyhow, bail, Result};
+
+/*
+Each chamber row is a bitmask with bit c set when column c holds stopped rock, and rocks are a
+bitmask per row shifted left by their x position. Collisions are then just an AND between the
+rock rows and the chamber rows they overlap, and nothing is written to the chamber until the
+rock comes to rest.
+*/
 #[derive(Debug)]
 struct Rock {
-    rows: usize,
+    // Bottom row first
+    rows: Vec<u8>,
     cols: usize,
-    x: usize,
-    y: usize,
-    data: [[u8; 4]; 4],
 }
 
-fn main() -> Result<()> {
-    let input = std::fs::read_to_string("./inputs/day17.txt")?;
-    let jets = input
-        .lines()
-        .flat_map(|line| line.chars().collect::<Vec<char>>())
-        .collect::<Vec<char>>();
-    // _
-    let rock_one = Rock {
-        rows: 1,
-        cols: 4,
-        data: [[1, 1, 1, 1], [0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
-        x: 0,
-        y: 0,
-    };
+const DEFAULT_CHAMBER_WIDTH: usize = 7;
+// One bit per column in a u8 row
+const MAX_CHAMBER_WIDTH: usize = 8;
+// Rows below the highest point looked at when comparing chamber states
+const SURFACE_DEPTH: usize = 64;
+
+// The five puzzle rocks in the same ASCII-art format accepted by `--rocks <file>`
+const DEFAULT_ROCKS: &str = r#"####
+
+.#.
+###
+.#.
+
+..#
+..#
+###
+
+#
+#
+#
+#
+
+##
+##"#;
+
+// Rocks are separated by blank lines, with '#' for rock and '.' for empty space. Rows are
+// listed top to bottom, the same way they are drawn in the puzzle description.
+fn parse_rocks(input: &str, width: usize) -> Result<Vec<Rock>> {
+    if width > MAX_CHAMBER_WIDTH {
+        bail!(
+            "chamber can be at most {} wide, got {}",
+            MAX_CHAMBER_WIDTH,
+            width
+        );
+    }
 
-    // +
-    let rock_two = Rock {
-        rows: 3,
-        cols: 3,
-        data: [[0, 1, 0, 0], [1, 1, 1, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
-        x: 0,
-        y: 0,
-    };
+    let rocks = input
+        .split("\n\n")
+        .map(|shape| {
+            let lines = shape
+                .lines()
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>();
+            let cols = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+            if cols == 0 {
+                bail!("empty rock shape");
+            }
+            if cols > width {
+                bail!(
+                    "rock is {} wide but the chamber is only {} wide",
+                    cols,
+                    width
+                );
+            }
 
-    // J
-    let rock_three = Rock {
-        rows: 3,
-        cols: 3,
-        data: [[0, 0, 1, 0], [0, 0, 1, 0], [1, 1, 1, 0], [0, 0, 0, 0]],
-        x: 0,
-        y: 0,
-    };
+            let mut rows = vec![0u8; lines.len()];
+            for (i, line) in lines.iter().rev().enumerate() {
+                for (j, c) in line.chars().enumerate() {
+                    match c {
+                        '#' => rows[i] |= 1 << j,
+                        '.' => {}
+                        _ => bail!("unexpected {:?} in rock shape", c),
+                    }
+                }
+            }
 
-    // I
-    let rock_four = Rock {
-        rows: 4,
-        cols: 1,
-        data: [[1, 0, 0, 0], [1, 0, 0, 0], [1, 0, 0, 0], [1, 0, 0, 0]],
-        x: 0,
-        y: 0,
-    };
+            Ok(Rock { rows, cols })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if rocks.is_empty() {
+        bail!("no rocks found");
+    }
+    Ok(rocks)
+}
+
+// The jet pattern, ignoring line breaks and other whitespace
+fn parse_jets(input: &str) -> Result<Vec<char>> {
+    let jets = input
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '<' | '>' => Ok(c),
+            _ => bail!("unexpected {:?} in the jet pattern", c),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if jets.is_empty() {
+        bail!("no jets in the jet pattern");
+    }
+    Ok(jets)
+}
+
+fn main() -> Result<()> {
+    let mut width = DEFAULT_CHAMBER_WIDTH;
+    let mut rocks_file = None;
+
+    let usage = "usage: day17 [--rocks <file>] [--width <columns>]";
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or_else(|| anyhow!(usage))?;
+        match arg.as_str() {
+            "--rocks" => rocks_file = Some(value),
+            "--width" => width = value.parse()?,
+            _ => bail!(usage),
+        }
+    }
 
-    // o
-    let rock_five = Rock {
-        rows: 2,
-        cols: 2,
-        data: [[1, 1, 0, 0], [1, 1, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
-        x: 0,
-        y: 0,
+    let shapes = match rocks_file {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => DEFAULT_ROCKS.to_string(),
     };
+    let rocks = parse_rocks(&shapes, width)?;
 
-    let mut rocks = vec![rock_one, rock_two, rock_three, rock_four, rock_five];
+    let input = std::fs::read_to_string("./inputs/day17.txt")?;
+    let jets = parse_jets(&input)?;
 
-    let part1_highest = solution(&jets, &mut rocks, 2022u64);
-    let part2_highest = solution(&jets, &mut rocks, 1_000_000_000_000u64);
+    let part1_highest = solution(&jets, &rocks, width, 2022u64);
+    let part2_highest = solution(&jets, &rocks, width, 1_000_000_000_000u64);
     println!("Part 1: {}", part1_highest);
     println!("Part 2: {}", part2_highest);
     Ok(())
 }
 
-fn solution(jets: &Vec<char>, rocks: &mut Vec<Rock>, max_rocks: u64) -> u64 {
-    let mut chamber: Vec<Vec<u8>> = vec![];
-    let mut seen = HashMap::<(usize, usize, [u32; 7]), (u64, usize)>::new();
+#[derive(Debug)]
+struct Chamber {
+    width: usize,
+    rows: Vec<u8>,
+    // Height of the tallest stopped rock in each column, kept up to date as rocks land
+    heights: Vec<usize>,
+    highest: usize,
+}
 
-    let mut highest = 0;
+impl Chamber {
+    fn new(width: usize) -> Self {
+        Chamber {
+            width,
+            rows: vec![],
+            heights: vec![0; width],
+            highest: 0,
+        }
+    }
 
-    let jet_count = jets.len();
-    let mut jet = 0;
+    fn collides(&self, rock: &Rock, x: usize, y: usize) -> bool {
+        rock.rows
+            .iter()
+            .zip(self.rows.iter().skip(y))
+            .any(|(&rock_row, &row)| (rock_row << x) & row != 0)
+    }
 
-    let rock_count = rocks.len();
-    let mut r = 0u64;
+    fn settle(&mut self, rock: &Rock, x: usize, y: usize) {
+        if self.rows.len() < y + rock.rows.len() {
+            self.rows.resize(y + rock.rows.len(), 0);
+        }
 
-    let mut added_highest = 0u64;
-    while r < max_rocks {
-        let rock_index = (r % rock_count as u64) as usize;
-        let rock = &mut rocks[rock_index];
+        for (i, &rock_row) in rock.rows.iter().enumerate() {
+            let mask = rock_row << x;
+            self.rows[y + i] |= mask;
+            for col in 0..self.width {
+                if mask & (1 << col) != 0 {
+                    self.heights[col] = self.heights[col].max(y + i + 1);
+                }
+            }
+        }
+        self.highest = self.highest.max(y + rock.rows.len());
+    }
 
-        add_rock(rock, &mut chamber, highest);
+    // Drops a rock until it comes to rest, consuming jets as it goes. Returns the row the bottom
+    // of the rock rests on.
+    fn drop_rock(&mut self, rock: &Rock, jets: &[char], jet: &mut usize) -> usize {
+        // Rocks appear two units from the left wall, unless they are too wide for that
+        let mut x = std::cmp::min(2, self.width - rock.cols);
+        let mut y = self.highest + 3;
 
         loop {
-            let can_move = move_rock(rock, jets[jet], &mut chamber, &mut highest);
-
-            if !can_move {
-                let top_view = get_topography(&chamber);
-                if seen.contains_key(&(rock_index, jet, top_view)) {
-                    let (old_r, old_highest) = seen.get(&(rock_index, jet, top_view)).unwrap();
-                    let highest_delta = highest - old_highest;
-                    let r_delta = r - old_r;
-                    let skip_amount = (max_rocks - r) / r_delta;
-                    added_highest += skip_amount * highest_delta as u64;
-                    r += skip_amount * r_delta;
+            let new_x = match jets[*jet] {
+                '<' if x > 0 => x - 1,
+                '>' if x + rock.cols < self.width => x + 1,
+                _ => x,
+            };
+            *jet = (*jet + 1) % jets.len();
+            if new_x != x && !self.collides(rock, new_x, y) {
+                x = new_x;
+            }
+
+            if y == 0 || self.collides(rock, x, y - 1) {
+                self.settle(rock, x, y);
+                return y;
+            }
+            y -= 1;
+        }
+    }
+
+    // Column depths below the highest point, capped to the rows a signature looks at
+    fn topography(&self) -> Vec<usize> {
+        self.heights
+            .iter()
+            .map(|h| (self.highest - h).min(SURFACE_DEPTH))
+            .collect()
+    }
+
+    /*
+    Column depths miss rock that can slide in under an overhang, so states are also compared on
+    the open cells falling rock can reach. Rock only ever moves left, right or down, so one pass
+    from the top finds them row by row. Rows are listed from the top down, at most SURFACE_DEPTH
+    of them, with a bit set for each cell rock can't get to.
+    */
+    fn surface(&self) -> Vec<u8> {
+        let full = ((1u16 << self.width) - 1) as u8;
+        let mut surface = vec![];
+        let mut reached = full;
+        for y in (self.highest.saturating_sub(SURFACE_DEPTH)..self.highest).rev() {
+            // Drop into the open cells below, then spread sideways through them
+            let open = !self.rows[y] & full;
+            reached &= open;
+            loop {
+                let spread = (reached | reached << 1 | reached >> 1) & open;
+                if spread == reached {
+                    break;
                 }
-                seen.insert((rock_index, jet, top_view), (r, highest));
+                reached = spread;
             }
-            jet = (jet + 1) % jet_count;
-            if !can_move {
+            if reached == 0 {
                 break;
             }
+            surface.push(!reached & full);
+        }
+        surface
+    }
+}
+
+/*
+Two states with the same rock, jet and signature only play out the same way if nothing below the
+signature's rows matters. So a repeat is only used once every rock since the first sighting came
+to rest inside those rows, which makes the skip exact however deep the open columns go.
+*/
+fn solution(jets: &[char], rocks: &[Rock], width: usize, max_rocks: u64) -> u64 {
+    type Key = (usize, usize, Vec<usize>, Vec<u8>);
+    let mut chamber = Chamber::new(width);
+    // Rocks dropped and the highest point when each state was last seen
+    let mut seen = HashMap::<Key, (usize, usize)>::new();
+    // The row each dropped rock came to rest on
+    let mut rested = vec![];
+
+    let mut jet = 0;
+    let mut r = 0u64;
+    let mut added_highest = 0u64;
+
+    while r < max_rocks {
+        let rock_index = (r % rocks.len() as u64) as usize;
+        rested.push(chamber.drop_rock(&rocks[rock_index], jets, &mut jet));
+        let dropped = rested.len();
+
+        let key = (rock_index, jet, chamber.topography(), chamber.surface());
+        if let Some(&(old_dropped, old_highest)) = seen.get(&key) {
+            // Rocks only look at the row under them on top of their own
+            let lowest = old_highest.saturating_sub(SURFACE_DEPTH) + 1;
+            if rested[old_dropped..].iter().all(|&y| y >= lowest) {
+                let highest_delta = chamber.highest - old_highest;
+                let r_delta = (dropped - old_dropped) as u64;
+                // rock r has already landed, so only max_rocks - r - 1 are left to place
+                let skip_amount = (max_rocks - r - 1) / r_delta;
+                added_highest += skip_amount * highest_delta as u64;
+                r += skip_amount * r_delta;
+            }
         }
+        seen.insert(key, (dropped, chamber.highest));
         r += 1;
     }
 
-    added_highest + highest as u64
+    added_highest + chamber.highest as u64
 }
 
-fn add_rock(rock: &mut Rock, chamber: &mut Vec<Vec<u8>>, highest: usize) {
-    if chamber.len() == 0 {
-        let d = rock.rows + 3;
+// Reference for `solution` that drops every single rock instead of skipping repeats
+#[cfg(test)]
+fn solution_plain(jets: &[char], rocks: &[Rock], width: usize, max_rocks: u64) -> u64 {
+    let mut chamber = Chamber::new(width);
+    let mut jet = 0;
+    for r in 0..max_rocks {
+        chamber.drop_rock(&rocks[(r % rocks.len() as u64) as usize], jets, &mut jet);
+    }
+    chamber.highest as u64
+}
 
-        for _ in 0..d {
-            chamber.push(vec![0; 7]);
-        }
-    } else if chamber.len() > 0 && chamber.len() - highest < rock.rows + 3 {
-        let d = rock.rows + 3 - (chamber.len() - highest);
+#[allow(dead_code)]
+const TEST_INPUT: &str = r#">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>"#;
 
-        for _ in 0..d {
-            chamber.push(vec![0; 7]);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aoc2022::generate;
+    use proptest::prelude::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn jets() -> Vec<char> {
+        TEST_INPUT.chars().collect()
     }
 
-    rock.x = 2;
-    // deal with zero index "highest" for first rock
-    if highest == 0 {
-        rock.y = (rock.rows + 3) - 1;
-    } else {
-        rock.y = (highest - 1) + (rock.rows + 3);
+    #[test]
+    fn test_part1() {
+        let rocks = parse_rocks(DEFAULT_ROCKS, DEFAULT_CHAMBER_WIDTH).unwrap();
+        assert_eq!(solution(&jets(), &rocks, DEFAULT_CHAMBER_WIDTH, 2022), 3068);
     }
 
-    for i in 0..rock.rows {
-        for j in 0..rock.cols {
-            chamber[rock.y - i][rock.x + j] = rock.data[i][j];
-        }
+    #[test]
+    fn test_part2() {
+        let rocks = parse_rocks(DEFAULT_ROCKS, DEFAULT_CHAMBER_WIDTH).unwrap();
+        assert_eq!(
+            solution(&jets(), &rocks, DEFAULT_CHAMBER_WIDTH, 1_000_000_000_000),
+            1514285714288
+        );
     }
-}
 
-fn move_rock(
-    rock: &mut Rock,
-    hot_gas: char,
-    chamber: &mut Vec<Vec<u8>>,
-    highest: &mut usize,
-) -> bool {
-    let mut new_x = rock.x;
-    let mut new_y = rock.y;
-    // gas tries to move rock < or >
-    if hot_gas == '<' && rock.x > 0 {
-        // move rock left
-        new_x = rock.x - 1;
-    } else if hot_gas == '>' && rock.x < chamber[0].len() - rock.cols {
-        // move rock right
-        new_x = rock.x + 1;
-    }
-
-    if new_x != rock.x && can_move(rock, chamber, new_x, new_y) {
-        clear_rock(rock, chamber);
-        do_move(rock, chamber, new_x, new_y);
-    }
-
-    // move rock down
-    if rock.y - (rock.rows - 1) > 0 {
-        new_y = rock.y - 1;
-    }
-
-    if new_y != rock.y && can_move(rock, chamber, rock.x, new_y) {
-        clear_rock(rock, chamber);
-        do_move(rock, chamber, rock.x, new_y);
-    } else {
-        // rock has stopped
-        for i in 0..rock.rows {
-            for j in 0..rock.cols {
-                if rock.data[i][j] == FALLING {
-                    chamber[rock.y - i][rock.x + j] = STOPPED;
-                }
-            }
-        }
-        *highest = std::cmp::max(*highest, rock.y + 1);
-        return false;
+    #[test]
+    fn test_custom_rocks() {
+        // A full-width slab stacks exactly one row per rock whatever the jets do
+        let rocks = parse_rocks("#####", 5).unwrap();
+        assert_eq!(solution(&jets(), &rocks, 5, 100), 100);
+
+        assert!(parse_rocks("########", DEFAULT_CHAMBER_WIDTH).is_err());
+        assert!(parse_rocks("#x#", DEFAULT_CHAMBER_WIDTH).is_err());
+        assert!(parse_rocks(DEFAULT_ROCKS, 9).is_err());
     }
-    true
-}
 
-fn can_move(rock: &Rock, chamber: &Vec<Vec<u8>>, to_x: usize, to_y: usize) -> bool {
-    for y in 0..rock.rows {
-        for x in 0..rock.cols {
-            if rock.data[y][x] == FALLING && chamber[to_y - y][to_x + x] == STOPPED {
-                return false;
-            }
-        }
+    #[test]
+    fn test_jets() {
+        assert_eq!(parse_jets("<>\n>\n").unwrap(), vec!['<', '>', '>']);
+        assert!(parse_jets("").is_err());
+        assert!(parse_jets(" \n\n").is_err());
+        assert!(parse_jets("<x>").is_err());
     }
-    true
-}
 
-// Find the unique signature of the topography of the chamber to find repeating patterns
-fn get_topography(chamber: &Vec<Vec<u8>>) -> [u32; 7] {
-    let mut topography = [0u32; 7];
-    for x in 0..chamber[0].len() {
-        for y in (0..chamber.len()).rev() {
-            if chamber[y][x] == STOPPED {
-                topography[x] = (chamber.len() - y) as u32;
-                break;
-            }
-        }
+    #[test]
+    fn test_overhangs() {
+        // Rock slides in under an overhang here, which column depths alone don't show
+        let jets = ">><<><<>><>><<>>>><><<>><><>>><<<><<"
+            .chars()
+            .collect::<Vec<_>>();
+        let rocks = parse_rocks(DEFAULT_ROCKS, 6).unwrap();
+        assert_eq!(solution(&jets, &rocks, 6, 57), 104);
+        assert_eq!(solution(&jets, &rocks, 6, 66), 121);
     }
-    topography
-}
 
-fn do_move(rock: &mut Rock, chamber: &mut Vec<Vec<u8>>, to_x: usize, to_y: usize) {
-    for y in 0..rock.rows {
-        for x in 0..rock.cols {
-            if rock.data[y][x] == FALLING {
-                chamber[to_y - y][to_x + x] = FALLING;
-            }
+    #[test]
+    fn test_one_sided_jets() {
+        // The right hand columns stay open down to the floor, so no signature covers them
+        let rocks = parse_rocks(DEFAULT_ROCKS, DEFAULT_CHAMBER_WIDTH).unwrap();
+        for jets in [['<'], ['>']] {
+            assert_eq!(
+                solution(&jets, &rocks, DEFAULT_CHAMBER_WIDTH, 2022),
+                solution_plain(&jets, &rocks, DEFAULT_CHAMBER_WIDTH, 2022)
+            );
+            assert!(solution(&jets, &rocks, DEFAULT_CHAMBER_WIDTH, 1_000_000_000_000) > 0);
         }
     }
-    rock.x = to_x;
-    rock.y = to_y;
-}
 
-fn clear_rock(rock: &Rock, chamber: &mut Vec<Vec<u8>>) {
-    for y in 0..rock.rows {
-        for x in 0..rock.cols {
-            if chamber[rock.y - y][rock.x + x] == FALLING {
-                chamber[rock.y - y][rock.x + x] = EMPTY;
-            }
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn cycle_skip_matches_plain(
+            seed in any::<u64>(),
+            length in 1..=40usize,
+            width in 4..=MAX_CHAMBER_WIDTH,
+            max_rocks in 0..=2022u64,
+        ) {
+            let jets = generate::day17(&mut StdRng::seed_from_u64(seed), length)
+                .trim_end()
+                .chars()
+                .collect::<Vec<_>>();
+            let rocks = parse_rocks(DEFAULT_ROCKS, width).unwrap();
+            prop_assert_eq!(
+                solution(&jets, &rocks, width, max_rocks),
+                solution_plain(&jets, &rocks, width, max_rocks)
+            );
         }
     }
 }
-
-#[allow(dead_code)]
-const TEST_INPUT: &str = r#">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>"#;
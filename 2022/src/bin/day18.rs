@@ -1,147 +1,205 @@
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
+
+type Cube = (i32, i32, i32);
+
+const NEIGHBORS: [Cube; 6] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+
+// Corners of the face of a unit cube in each NEIGHBORS direction, counter-clockwise when seen
+// from outside so the face normal points outwards
+const FACE_CORNERS: [[Cube; 4]; 6] = [
+    [(1, 0, 0), (1, 1, 0), (1, 1, 1), (1, 0, 1)],
+    [(0, 0, 0), (0, 0, 1), (0, 1, 1), (0, 1, 0)],
+    [(0, 1, 0), (0, 1, 1), (1, 1, 1), (1, 1, 0)],
+    [(0, 0, 0), (1, 0, 0), (1, 0, 1), (0, 0, 1)],
+    [(0, 0, 1), (1, 0, 1), (1, 1, 1), (0, 1, 1)],
+    [(0, 0, 0), (0, 1, 0), (1, 1, 0), (1, 0, 0)],
+];
 
 fn main() -> Result<()> {
-    let input = std::fs::read_to_string("./inputs/day18.txt")?;
-    let cubes = input
-        .lines()
-        .map(|line| {
-            let mut parts = line.split(",");
-            let x = parts.next().unwrap().parse::<i32>().unwrap();
-            let y = parts.next().unwrap().parse::<i32>().unwrap();
-            let z = parts.next().unwrap().parse::<i32>().unwrap();
-            (x, y, z)
-        })
-        .collect::<HashSet<_>>();
+    let mut obj = None;
+    let mut stl = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--obj" => obj = Some(args.next().context("--obj needs a file name")?),
+            "--stl" => stl = Some(args.next().context("--stl needs a file name")?),
+            _ => bail!("usage: day18 [--obj <file>] [--stl <file>]"),
+        }
+    }
 
-    let open_sides = part_one(&cubes);
+    let input = std::fs::read_to_string("./inputs/day18.txt")?;
+    let droplet = Droplet::new(parse_cubes(&input)?)?;
+    let faces = droplet.exterior_faces();
 
-    println!("Part 1: {}", open_sides);
+    println!("Part 1: {}", droplet.surface_area());
+    println!("Part 2: {}", faces.len());
 
-    let part2_sides = part_two(&cubes);
-    println!("Part 2: {}", part2_sides);
+    if let Some(file) = obj {
+        std::fs::write(&file, to_obj(&faces))
+            .with_context(|| format!("could not write {}", file))?;
+    }
+    if let Some(file) = stl {
+        std::fs::write(&file, to_stl(&faces))
+            .with_context(|| format!("could not write {}", file))?;
+    }
 
     Ok(())
 }
 
-fn part_one(cubes: &HashSet<(i32, i32, i32)>) -> i32 {
-    let mut open_sides = 0;
-    for cube in cubes.iter() {
-        if !cubes.contains(&(cube.0 + 1, cube.1, cube.2)) {
-            open_sides += 1;
-        }
+fn parse_cubes(input: &str) -> Result<HashSet<Cube>> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let coords = line
+                .split(',')
+                .map(|coord| coord.parse::<i32>())
+                .collect::<Result<Vec<_>, _>>()
+                .with_context(|| format!("invalid cube on line {}", i + 1))?;
+            match coords.as_slice() {
+                &[x, y, z] => Ok((x, y, z)),
+                _ => Err(anyhow!("line {} does not have three coordinates", i + 1)),
+            }
+        })
+        .collect()
+}
 
-        if !cubes.contains(&(cube.0 - 1, cube.1, cube.2)) {
-            open_sides += 1;
-        }
+fn offset(cube: Cube, d: Cube) -> Cube {
+    (cube.0 + d.0, cube.1 + d.1, cube.2 + d.2)
+}
 
-        if !cubes.contains(&(cube.0, cube.1 + 1, cube.2)) {
-            open_sides += 1;
-        }
+/*
+The bounding box is grown by one in every direction, so the air around the droplet is one
+connected shell. A single flood fill from a corner of that box then finds all of the outside
+air, and a face is on the exterior exactly when the cell beyond it was reached.
+*/
+#[derive(Debug)]
+struct Droplet {
+    cubes: HashSet<Cube>,
+    min: Cube,
+    max: Cube,
+}
 
-        if !cubes.contains(&(cube.0, cube.1 - 1, cube.2)) {
-            open_sides += 1;
+impl Droplet {
+    fn new(cubes: HashSet<Cube>) -> Result<Self> {
+        if cubes.is_empty() {
+            bail!("the droplet has no cubes");
         }
 
-        if !cubes.contains(&(cube.0, cube.1, cube.2 + 1)) {
-            open_sides += 1;
-        }
+        let bound = |axis: fn(&Cube) -> i32| {
+            let values = cubes.iter().map(axis);
+            (values.clone().min().unwrap() - 1, values.max().unwrap() + 1)
+        };
+        let (x, y, z) = (bound(|c| c.0), bound(|c| c.1), bound(|c| c.2));
 
-        if !cubes.contains(&(cube.0, cube.1, cube.2 - 1)) {
-            open_sides += 1;
-        }
+        Ok(Droplet {
+            cubes,
+            min: (x.0, y.0, z.0),
+            max: (x.1, y.1, z.1),
+        })
     }
-    open_sides
-}
 
-fn can_get_out(
-    x: i32,
-    y: i32,
-    z: i32,
-    cubes: &HashSet<(i32, i32, i32)>,
-    min: &(i32, i32, i32),
-    max: &(i32, i32, i32),
-) -> bool {
-    let mut queue = VecDeque::new();
-
-    queue.push_back((x, y, z));
-    let mut seen = HashSet::new();
-    while !queue.is_empty() {
-        let (x, y, z) = queue.pop_front().unwrap();
-        if seen.contains(&(x, y, z)) {
-            continue;
-        }
-        seen.insert((x, y, z));
+    fn in_bounds(&self, (x, y, z): Cube) -> bool {
+        (self.min.0..=self.max.0).contains(&x)
+            && (self.min.1..=self.max.1).contains(&y)
+            && (self.min.2..=self.max.2).contains(&z)
+    }
 
-        if cubes.contains(&(x, y, z)) {
-            continue;
-        }
+    /// Faces not touching another cube, including those facing trapped air pockets.
+    fn surface_area(&self) -> usize {
+        self.cubes
+            .iter()
+            .flat_map(|&cube| NEIGHBORS.iter().map(move |&d| offset(cube, d)))
+            .filter(|neighbor| !self.cubes.contains(neighbor))
+            .count()
+    }
 
-        if (x > max.0 || x < min.0) || (y > max.1 || y < min.1) || (z > max.2 || z < min.2) {
-            return true;
-        }
+    /// All air in the padded bounding box that can reach the outside.
+    fn exterior(&self) -> HashSet<Cube> {
+        let mut outside = HashSet::from([self.min]);
+        let mut queue = VecDeque::from([self.min]);
 
-        for (dx, dy, dz) in &[
-            (1, 0, 0),
-            (-1, 0, 0),
-            (0, 1, 0),
-            (0, -1, 0),
-            (0, 0, 1),
-            (0, 0, -1),
-        ] {
-            queue.push_back((x + dx, y + dy, z + dz));
+        while let Some(cell) = queue.pop_front() {
+            for &d in NEIGHBORS.iter() {
+                let next = offset(cell, d);
+                if self.in_bounds(next) && !self.cubes.contains(&next) && outside.insert(next) {
+                    queue.push_back(next);
+                }
+            }
         }
+        outside
     }
 
-    false
+    /// Every face on the outside of the droplet, as the cube and its index into NEIGHBORS.
+    fn exterior_faces(&self) -> Vec<(Cube, usize)> {
+        let outside = self.exterior();
+        let mut faces = self
+            .cubes
+            .iter()
+            .flat_map(|&cube| (0..NEIGHBORS.len()).map(move |dir| (cube, dir)))
+            .filter(|&(cube, dir)| outside.contains(&offset(cube, NEIGHBORS[dir])))
+            .collect::<Vec<_>>();
+        // Sorted so exported meshes are the same from run to run
+        faces.sort();
+        faces
+    }
 }
 
-fn part_two(cubes: &HashSet<(i32, i32, i32)>) -> i32 {
-    let mut min_x = 0i32;
-    let mut max_x = 0i32;
-    let mut min_y = 0i32;
-    let mut max_y = 0i32;
-    let mut min_z = 0i32;
-    let mut max_z = 0i32;
-    for cube in cubes.iter() {
-        min_x = min_x.min(cube.0);
-        max_x = max_x.max(cube.0);
-        min_y = min_y.min(cube.1);
-        max_y = max_y.max(cube.1);
-        min_z = min_z.min(cube.2);
-        max_z = max_z.max(cube.2);
-    }
-    let mut cache = HashMap::new();
-    let mut open_sides = 0;
-    for cube in cubes.iter() {
-        for (dx, dy, dz) in &[
-            (1, 0, 0),
-            (-1, 0, 0),
-            (0, 1, 0),
-            (0, -1, 0),
-            (0, 0, 1),
-            (0, 0, -1),
-        ] {
-            let key = (cube.0 + dx, cube.1 + dy, cube.2 + dz);
-            let can_escape = cache.entry(key).or_insert_with(|| {
-                can_get_out(
-                    cube.0 + dx,
-                    cube.1 + dy,
-                    cube.2 + dz,
-                    cubes,
-                    &(min_x, min_y, min_z),
-                    &(max_x, max_y, max_z),
-                )
+fn face_corners(cube: Cube, dir: usize) -> [Cube; 4] {
+    FACE_CORNERS[dir].map(|corner| offset(cube, corner))
+}
+
+/// Wavefront OBJ with one quad per face, sharing vertices between faces.
+fn to_obj(faces: &[(Cube, usize)]) -> String {
+    let mut vertices = HashMap::new();
+    let mut out = String::new();
+    let mut quads = String::new();
+
+    for &(cube, dir) in faces.iter() {
+        let mut indices = vec![];
+        for corner in face_corners(cube, dir) {
+            let next = vertices.len() + 1;
+            let index = *vertices.entry(corner).or_insert_with(|| {
+                writeln!(out, "v {} {} {}", corner.0, corner.1, corner.2).unwrap();
+                next
             });
+            indices.push(index.to_string());
+        }
+        writeln!(quads, "f {}", indices.join(" ")).unwrap();
+    }
+
+    out + &quads
+}
+
+/// ASCII STL, which only has triangles, so each face is split in two.
+fn to_stl(faces: &[(Cube, usize)]) -> String {
+    let mut out = "solid droplet\n".to_string();
 
-            if *can_escape {
-                open_sides += 1;
+    for &(cube, dir) in faces.iter() {
+        let (nx, ny, nz) = NEIGHBORS[dir];
+        let [a, b, c, d] = face_corners(cube, dir);
+        for triangle in [[a, b, c], [a, c, d]] {
+            writeln!(out, "  facet normal {} {} {}", nx, ny, nz).unwrap();
+            writeln!(out, "    outer loop").unwrap();
+            for (x, y, z) in triangle {
+                writeln!(out, "      vertex {} {} {}", x, y, z).unwrap();
             }
+            writeln!(out, "    endloop").unwrap();
+            writeln!(out, "  endfacet").unwrap();
         }
     }
 
-    open_sides
+    out + "endsolid droplet\n"
 }
 
 #[allow(dead_code)]
@@ -158,3 +216,71 @@ const TEST_INPUT: &str = r#"2,2,2
 3,2,5
 2,1,5
 2,3,5"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parts() {
+        let droplet = Droplet::new(parse_cubes(TEST_INPUT).unwrap()).unwrap();
+        assert_eq!(droplet.surface_area(), 64);
+        assert_eq!(droplet.exterior_faces().len(), 58);
+    }
+
+    #[test]
+    fn test_negative_coordinates() {
+        let cubes = parse_cubes(TEST_INPUT)
+            .unwrap()
+            .into_iter()
+            .map(|(x, y, z)| (x - 10, y - 20, z - 30))
+            .collect();
+        let droplet = Droplet::new(cubes).unwrap();
+        assert_eq!(droplet.surface_area(), 64);
+        assert_eq!(droplet.exterior_faces().len(), 58);
+    }
+
+    #[test]
+    fn test_hollow_cube() {
+        // A 3x3x3 shell around one trapped cell of air
+        let mut cubes = HashSet::new();
+        for x in 0..3 {
+            for y in 0..3 {
+                for z in 0..3 {
+                    if (x, y, z) != (1, 1, 1) {
+                        cubes.insert((x, y, z));
+                    }
+                }
+            }
+        }
+        let droplet = Droplet::new(cubes).unwrap();
+        assert_eq!(droplet.surface_area(), 60);
+        assert_eq!(droplet.exterior_faces().len(), 54);
+    }
+
+    #[test]
+    fn test_mesh_export() {
+        let droplet = Droplet::new(parse_cubes("1,1,1").unwrap()).unwrap();
+        let faces = droplet.exterior_faces();
+
+        let obj = to_obj(&faces);
+        assert_eq!(obj.lines().filter(|line| line.starts_with("v ")).count(), 8);
+        assert_eq!(obj.lines().filter(|line| line.starts_with("f ")).count(), 6);
+
+        let stl = to_stl(&faces);
+        assert_eq!(stl.matches("facet normal").count(), 12);
+        assert!(stl.contains("facet normal 0 0 -1"));
+
+        let droplet = Droplet::new(parse_cubes(TEST_INPUT).unwrap()).unwrap();
+        let faces = droplet.exterior_faces();
+        assert_eq!(to_obj(&faces).matches("\nf ").count(), 58);
+        assert_eq!(to_stl(&faces).matches("endfacet").count(), 116);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(parse_cubes("1,2").is_err());
+        assert!(parse_cubes("1,2,x").is_err());
+        assert!(Droplet::new(HashSet::new()).is_err());
+    }
+}
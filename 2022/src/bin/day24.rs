@@ -0,0 +1,271 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+
+fn main() -> Result<()> {
+    let input = std::fs::read_to_string("./inputs/day24.txt")?;
+    let basin = input.parse::<Basin>()?;
+
+    let part1 = basin
+        .trip(1)
+        .context("no path from the entrance to the exit")?;
+    println!("Part 1: {}", part1);
+    let part2 = basin
+        .trip(3)
+        .context("no path back for the snacks and out again")?;
+    println!("Part 2: {}", part2);
+
+    Ok(())
+}
+
+/*
+Blizzards never interact, so instead of simulating the valley minute by minute we keep one
+bitmask per row for the horizontal blizzards and one per column for the vertical ones, holding
+their starting positions. A cell is occupied at time t if a blizzard started t steps "upwind"
+of it, which is just a couple of modular index lookups.
+*/
+#[derive(Debug)]
+struct Basin {
+    width: usize,
+    height: usize,
+    entrance: (usize, usize),
+    exit: (usize, usize),
+    // The blizzard layout repeats every lcm(width, height) minutes
+    period: usize,
+    right: Vec<u128>,
+    left: Vec<u128>,
+    down: Vec<u128>,
+    up: Vec<u128>,
+}
+
+impl FromStr for Basin {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Everything below indexes lines by byte
+        if !s.is_ascii() {
+            bail!("basin drawing can only contain ASCII characters");
+        }
+        let lines = s.lines().collect::<Vec<_>>();
+        if lines.len() < 3 {
+            bail!("basin needs at least a top wall, one row and a bottom wall");
+        }
+
+        if lines[0].len() < 3 {
+            bail!("top wall {:?} leaves no room for the valley", lines[0]);
+        }
+        let width = lines[0].len() - 2;
+        let height = lines.len() - 2;
+        if width > 128 || height > 128 {
+            bail!(
+                "basin of {}x{} is too large, at most 128x128 is supported",
+                width,
+                height
+            );
+        }
+
+        // A solid wall with a single gap opening onto the valley, not onto the side walls
+        let gap = |line: &str, wall: &str| {
+            if line.len() != width + 2 {
+                bail!(
+                    "{} wall has length {}, expected {}",
+                    wall,
+                    line.len(),
+                    width + 2
+                );
+            }
+            if let Some(col) = line.find(|c| c != '#' && c != '.') {
+                bail!("unexpected {:?} in the {} wall", &line[col..=col], wall);
+            }
+            match line
+                .match_indices('.')
+                .map(|(col, _)| col)
+                .collect::<Vec<_>>()[..]
+            {
+                [col] if (1..=width).contains(&col) => Ok(col),
+                [col] => bail!("gap in the {} wall at col {} is in a corner", wall, col),
+                [] => bail!("no gap in the {} wall", wall),
+                _ => bail!("more than one gap in the {} wall", wall),
+            }
+        };
+        let entrance = (0, gap(lines[0], "top")?);
+        let exit = (height + 1, gap(lines[height + 1], "bottom")?);
+
+        let mut right = vec![0u128; height];
+        let mut left = vec![0u128; height];
+        let mut down = vec![0u128; width];
+        let mut up = vec![0u128; width];
+
+        for (r, line) in lines[1..=height].iter().enumerate() {
+            if line.len() != width + 2 {
+                bail!(
+                    "row {} has length {}, expected {}",
+                    r + 1,
+                    line.len(),
+                    width + 2
+                );
+            }
+            if !line.starts_with('#') || !line.ends_with('#') {
+                bail!("row {} isn't enclosed by '#' side walls", r + 1);
+            }
+            for (c, ch) in line[1..=width].chars().enumerate() {
+                match ch {
+                    '>' => right[r] |= 1 << c,
+                    '<' => left[r] |= 1 << c,
+                    'v' => down[c] |= 1 << r,
+                    '^' => up[c] |= 1 << r,
+                    '.' => {}
+                    _ => bail!("unexpected {:?} at row {}, col {}", ch, r + 1, c + 1),
+                }
+            }
+        }
+
+        Ok(Basin {
+            width,
+            height,
+            entrance,
+            exit,
+            period: num::integer::lcm(width, height),
+            right,
+            left,
+            down,
+            up,
+        })
+    }
+}
+
+impl Basin {
+    // Coordinates include the surrounding walls, so the interior is 1..=height x 1..=width
+    fn is_open(&self, row: usize, col: usize, time: usize) -> bool {
+        if (row, col) == self.entrance || (row, col) == self.exit {
+            return true;
+        }
+        if row == 0 || row > self.height || col == 0 || col > self.width {
+            return false;
+        }
+
+        let (r, c) = (row - 1, col - 1);
+        let (w, h) = (self.width, self.height);
+        let (tw, th) = (time % w, time % h);
+
+        let bit = |mask: u128, i: usize| mask >> i & 1 == 1;
+        !(bit(self.right[r], (c + w - tw) % w)
+            || bit(self.left[r], (c + tw) % w)
+            || bit(self.down[c], (r + h - th) % h)
+            || bit(self.up[c], (r + th) % h))
+    }
+
+    /// Earliest time `to` can be reached when leaving `from` at `start`.
+    fn shortest(&self, from: (usize, usize), to: (usize, usize), start: usize) -> Option<usize> {
+        let cols = self.width + 2;
+        let cells = (self.height + 2) * cols;
+        let mut seen = vec![false; cells * self.period];
+        let mut queue = VecDeque::from([(from, start)]);
+
+        while let Some(((row, col), time)) = queue.pop_front() {
+            if (row, col) == to {
+                return Some(time);
+            }
+
+            let next = time + 1;
+            let moves = [
+                Some((row, col)),
+                row.checked_sub(1).map(|r| (r, col)),
+                (row <= self.height).then_some((row + 1, col)),
+                col.checked_sub(1).map(|c| (row, c)),
+                Some((row, col + 1)),
+            ];
+            for (r, c) in moves.into_iter().flatten() {
+                if !self.is_open(r, c, next) {
+                    continue;
+                }
+                let key = (next % self.period) * cells + r * cols + c;
+                if !seen[key] {
+                    seen[key] = true;
+                    queue.push_back(((r, c), next));
+                }
+            }
+        }
+        None
+    }
+
+    /// Total time for `legs` crossings, alternating entrance -> exit and back again.
+    fn trip(&self, legs: usize) -> Option<usize> {
+        let (mut from, mut to) = (self.entrance, self.exit);
+        let mut time = 0;
+        for _ in 0..legs {
+            time = self.shortest(from, to, time)?;
+            std::mem::swap(&mut from, &mut to);
+        }
+        Some(time)
+    }
+}
+
+#[allow(dead_code)]
+const TEST_INPUT: &str = r#"#.######
+#>>.<^<#
+#.<..<<#
+#>v.><>#
+#<^v^^>#
+######.#"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aoc2022::generate;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_part1() {
+        let basin = TEST_INPUT.parse::<Basin>().unwrap();
+        assert_eq!(basin.trip(1), Some(18));
+    }
+
+    #[test]
+    fn test_part2() {
+        let basin = TEST_INPUT.parse::<Basin>().unwrap();
+        assert_eq!(basin.trip(3), Some(54));
+    }
+
+    #[test]
+    fn test_blizzards_wrap() {
+        let basin = TEST_INPUT.parse::<Basin>().unwrap();
+        // The '>' at row 1, col 1 is at col 3 after two minutes and back after a full period
+        assert!(!basin.is_open(1, 3, 2));
+        assert!(!basin.is_open(1, 1, basin.period));
+    }
+
+    #[test]
+    fn test_generated() {
+        for seed in 0..20 {
+            let size = 3 + seed as usize * 3;
+            let input = generate::day24(&mut StdRng::seed_from_u64(seed), size);
+            let basin = input.parse::<Basin>().unwrap();
+            assert!(basin.trip(3).is_some(), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn test_invalid_walls() {
+        for invalid in [
+            "##\n##\n##",
+            ".#####\n#....#\n####.#",
+            "#.####\n#....#\n#####.",
+            "#.####\n#....#\n######",
+            "#.####\n#....#\n###.#",
+            "#.####\n#....#",
+            "#..###\n#....#\n####.#",
+            "#.####\nX....X\n####.#",
+            "#.####\n#....#\n##x#.#",
+            "#.####\n#é...#\n####.#",
+            "#.####\n#...é#\n####.#",
+        ] {
+            assert!(invalid.parse::<Basin>().is_err(), "{:?}", invalid);
+        }
+
+        // Walled in by blizzards that never leave the entrance's column
+        let basin = "#.#\n#v#\n#^#\n#.#".parse::<Basin>().unwrap();
+        assert_eq!(basin.trip(1), None);
+    }
+}
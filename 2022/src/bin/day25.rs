@@ -0,0 +1,279 @@
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign};
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+
+fn main() -> Result<()> {
+    let input = std::fs::read_to_string("./inputs/day25.txt")?;
+    let numbers = input
+        .lines()
+        .map(|line| line.parse::<Snafu>())
+        .collect::<Result<Vec<_>>>()?;
+
+    let total = numbers.iter().sum::<Snafu>();
+    println!("Part 1: {}", total);
+
+    Ok(())
+}
+
+/*
+A number in balanced base 5. Digits are stored least significant first, each in -2..=2, with no
+trailing (most significant) zeros, so zero is the empty digit list and every value has exactly
+one representation.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+struct Snafu {
+    digits: Vec<i8>,
+}
+
+impl Snafu {
+    fn from_digits(mut digits: Vec<i8>) -> Self {
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        Snafu { digits }
+    }
+
+    fn to_i128(&self) -> Option<i128> {
+        // Intermediate values can leave the i128 range even when the result is in range (e.g.
+        // i128::MIN), so accumulate with wrapping and check the result converts back.
+        let n = self
+            .digits
+            .iter()
+            .rev()
+            .fold(0i128, |acc, &d| acc.wrapping_mul(5).wrapping_add(d as i128));
+        (Snafu::from(n) == *self).then_some(n)
+    }
+}
+
+impl FromStr for Snafu {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            bail!("empty SNAFU number");
+        }
+
+        let digits = s
+            .chars()
+            .rev()
+            .map(|c| match c {
+                '2' => Ok(2),
+                '1' => Ok(1),
+                '0' => Ok(0),
+                '-' => Ok(-1),
+                '=' => Ok(-2),
+                _ => Err(anyhow!("invalid SNAFU digit {:?} in {:?}", c, s)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Snafu::from_digits(digits))
+    }
+}
+
+impl fmt::Display for Snafu {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.digits.is_empty() {
+            return write!(f, "0");
+        }
+        for d in self.digits.iter().rev() {
+            let c = match d {
+                2 => '2',
+                1 => '1',
+                0 => '0',
+                -1 => '-',
+                -2 => '=',
+                _ => unreachable!("digit out of range"),
+            };
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<i128> for Snafu {
+    fn from(mut n: i128) -> Self {
+        let mut digits = vec![];
+        while n != 0 {
+            // div/rem_euclid rather than (n - d) / 5, which overflows for i128::MIN
+            let mut q = n.div_euclid(5);
+            let mut d = n.rem_euclid(5) as i8;
+            if d > 2 {
+                d -= 5;
+                q += 1;
+            }
+            digits.push(d);
+            n = q;
+        }
+        Snafu { digits }
+    }
+}
+
+impl From<i64> for Snafu {
+    fn from(n: i64) -> Self {
+        Snafu::from(n as i128)
+    }
+}
+
+impl TryFrom<&Snafu> for i128 {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Snafu) -> Result<Self, Self::Error> {
+        value
+            .to_i128()
+            .ok_or_else(|| anyhow!("{} does not fit in an i128", value))
+    }
+}
+
+impl TryFrom<&Snafu> for i64 {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Snafu) -> Result<Self, Self::Error> {
+        value
+            .to_i128()
+            .and_then(|n| i64::try_from(n).ok())
+            .ok_or_else(|| anyhow!("{} does not fit in an i64", value))
+    }
+}
+
+// Addition is done digit by digit with a balanced carry, so sums never pass through decimal
+impl Add<&Snafu> for &Snafu {
+    type Output = Snafu;
+
+    fn add(self, other: &Snafu) -> Snafu {
+        let len = self.digits.len().max(other.digits.len());
+        let mut digits = Vec::with_capacity(len + 1);
+        let mut carry = 0;
+
+        for i in 0..len {
+            let a = self.digits.get(i).copied().unwrap_or(0);
+            let b = other.digits.get(i).copied().unwrap_or(0);
+            let mut d = a + b + carry;
+            carry = 0;
+            if d > 2 {
+                d -= 5;
+                carry = 1;
+            } else if d < -2 {
+                d += 5;
+                carry = -1;
+            }
+            digits.push(d);
+        }
+        digits.push(carry);
+
+        Snafu::from_digits(digits)
+    }
+}
+
+impl Add for Snafu {
+    type Output = Snafu;
+
+    fn add(self, other: Snafu) -> Snafu {
+        &self + &other
+    }
+}
+
+impl AddAssign<&Snafu> for Snafu {
+    fn add_assign(&mut self, other: &Snafu) {
+        *self = &*self + other;
+    }
+}
+
+impl<'a> Sum<&'a Snafu> for Snafu {
+    fn sum<I: Iterator<Item = &'a Snafu>>(iter: I) -> Snafu {
+        iter.fold(Snafu::default(), |mut acc, n| {
+            acc += n;
+            acc
+        })
+    }
+}
+
+impl Sum for Snafu {
+    fn sum<I: Iterator<Item = Snafu>>(iter: I) -> Snafu {
+        iter.fold(Snafu::default(), |mut acc, n| {
+            acc += &n;
+            acc
+        })
+    }
+}
+
+#[allow(dead_code)]
+const TEST_INPUT: &str = r#"1=-0-2
+12111
+2=0=
+21
+2=01
+111
+20012
+112
+1=-1=
+1-12
+12
+1=
+122"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_part1() {
+        let total = TEST_INPUT
+            .lines()
+            .map(|line| line.parse::<Snafu>().unwrap())
+            .sum::<Snafu>();
+        assert_eq!(total.to_string(), "2=-1=0");
+        assert_eq!(i64::try_from(&total).unwrap(), 4890);
+    }
+
+    #[test]
+    fn test_conversions() {
+        for (decimal, snafu) in [
+            (0i64, "0"),
+            (3, "1="),
+            (8, "2="),
+            (2022, "1=11-2"),
+            (12345, "1-0---0"),
+            (314159265, "1121-1110-1=0"),
+        ] {
+            assert_eq!(Snafu::from(decimal).to_string(), snafu);
+            assert_eq!(
+                i64::try_from(&snafu.parse::<Snafu>().unwrap()).unwrap(),
+                decimal
+            );
+        }
+        for n in [i128::MIN, i128::MAX] {
+            assert_eq!(i128::try_from(&Snafu::from(n)).unwrap(), n);
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!("".parse::<Snafu>().is_err());
+        assert!("12a".parse::<Snafu>().is_err());
+        assert!(i64::try_from(&Snafu::from(i128::MAX)).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips_i64(n in any::<i64>()) {
+            let snafu = Snafu::from(n);
+            prop_assert_eq!(i64::try_from(&snafu).unwrap(), n);
+            prop_assert_eq!(snafu.to_string().parse::<Snafu>().unwrap(), snafu);
+        }
+
+        #[test]
+        fn round_trips_i128(n in any::<i128>()) {
+            prop_assert_eq!(i128::try_from(&Snafu::from(n)).unwrap(), n);
+        }
+
+        #[test]
+        fn add_matches_decimal(a in any::<i64>(), b in any::<i64>()) {
+            let sum = Snafu::from(a) + Snafu::from(b);
+            prop_assert_eq!(i128::try_from(&sum).unwrap(), a as i128 + b as i128);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use anyhow::{bail, Context, Result};
+
+use aoc2022::generate::{default_size, generate, DAYS};
+
+fn main() -> Result<()> {
+    let usage = "usage: generate <day> [--seed <n>] [--size <n>]";
+    let mut args = std::env::args().skip(1);
+    let day = match args.next() {
+        Some(day) => day
+            .trim_start_matches("day")
+            .parse::<u32>()
+            .context(usage)?,
+        None => bail!(usage),
+    };
+    if !DAYS.contains(&day) {
+        bail!("no generator for day {}, try one of {:?}", day, DAYS);
+    }
+
+    let mut seed = 0;
+    let mut size = default_size(day);
+    while let Some(arg) = args.next() {
+        let value = args.next().context(usage)?;
+        match arg.as_str() {
+            "--seed" => seed = value.parse()?,
+            "--size" => size = value.parse()?,
+            _ => bail!(usage),
+        }
+    }
+
+    print!("{}", generate(day, seed, size)?);
+    Ok(())
+}
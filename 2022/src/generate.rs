@@ -0,0 +1,867 @@
+/*
+Random puzzle inputs, for benchmarking the solutions and stress testing them on inputs larger or
+nastier than the one we were given. Every generator writes exactly the format the matching
+day's parser reads, and `size` scales whatever makes that day's input big: number of lines,
+grid width, and so on. The same seed and size always give the same input.
+*/
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::fmt::Write;
+
+use anyhow::{bail, Result};
+use rand::rngs::StdRng;
+use rand::seq::{IndexedRandom, SliceRandom};
+use rand::{Rng, RngExt, SeedableRng};
+
+/// Days that have a generator.
+pub const DAYS: [u32; 20] = [
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 24, 25,
+];
+
+/// A size giving inputs roughly as large as the real puzzle's.
+pub fn default_size(day: u32) -> usize {
+    match day {
+        1 => 250,
+        2..=4 => 300,
+        5 => 500,
+        6 => 4096,
+        7 => 400,
+        8 => 99,
+        9 => 2000,
+        10 => 140,
+        11 => 8,
+        12 => 160,
+        13 => 150,
+        14 => 150,
+        15 => 30,
+        16 => 60,
+        17 => 10_000,
+        18 => 2800,
+        24 => 120,
+        _ => 100,
+    }
+}
+
+pub fn generate(day: u32, seed: u64, size: usize) -> Result<String> {
+    let rng = &mut StdRng::seed_from_u64(seed);
+    Ok(match day {
+        1 => day01(rng, size),
+        2 => day02(rng, size),
+        3 => day03(rng, size),
+        4 => day04(rng, size),
+        5 => day05(rng, size),
+        6 => day06(rng, size),
+        7 => day07(rng, size),
+        8 => day08(rng, size),
+        9 => day09(rng, size),
+        10 => day10(rng, size),
+        11 => day11(rng, size),
+        12 => day12(rng, size),
+        13 => day13(rng, size),
+        14 => day14(rng, size),
+        15 => day15(rng, size, DAY15_MAX),
+        16 => day16(rng, size),
+        17 => day17(rng, size),
+        18 => day18(rng, size),
+        24 => day24(rng, size),
+        25 => day25(rng, size),
+        _ => bail!("no generator for day {}", day),
+    })
+}
+
+fn lowercase<R: Rng>(rng: &mut R) -> char {
+    rng.random_range('a'..='z')
+}
+
+/// `size` elves each carrying a handful of snacks.
+pub fn day01<R: Rng>(rng: &mut R, size: usize) -> String {
+    let elves = (0..size.max(1))
+        .map(|_| {
+            (0..rng.random_range(1..=15))
+                .map(|_| format!("{}\n", rng.random_range(1000..=70_000)))
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>();
+    elves.join("\n")
+}
+
+/// `size` rounds of the strategy guide.
+pub fn day02<R: Rng>(rng: &mut R, size: usize) -> String {
+    (0..size.max(1))
+        .map(|_| {
+            format!(
+                "{} {}\n",
+                rng.random_range('A'..='C'),
+                rng.random_range('X'..='Z')
+            )
+        })
+        .collect()
+}
+
+const ITEMS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// At least `size` rucksacks in groups of three, each with exactly one item in both
+/// compartments and each group with exactly one badge.
+pub fn day03<R: Rng>(rng: &mut R, size: usize) -> String {
+    let mut out = String::new();
+    for _ in 0..size.max(1).div_ceil(3) {
+        let badge = *ITEMS.choose(rng).unwrap();
+        let mut others = ITEMS
+            .iter()
+            .copied()
+            .filter(|&item| item != badge)
+            .collect::<Vec<_>>();
+        others.shuffle(rng);
+
+        // The elves don't share any items but the badge
+        for pool in others.chunks(others.len() / 3).take(3) {
+            let mut pool = pool.to_vec();
+            pool.push(badge);
+            pool.shuffle(rng);
+
+            // One item goes in both compartments and the rest are split between them
+            let shared = pool.pop().unwrap();
+            let (left_pool, right_pool) = pool.split_at(pool.len() / 2);
+            let len = rng.random_range(4..=16);
+            let mut fill = |pool: &[u8], must: Option<u8>| {
+                let mut items = vec![shared];
+                items.extend(must);
+                while items.len() < len {
+                    items.push(*pool.choose(rng).unwrap());
+                }
+                items.shuffle(rng);
+                items
+            };
+
+            // The badge has to be in there somewhere
+            let must = (shared != badge).then_some(badge);
+            let (left, right) = if left_pool.contains(&badge) {
+                (fill(left_pool, must), fill(right_pool, None))
+            } else {
+                (fill(left_pool, None), fill(right_pool, must))
+            };
+            out.push_str(&String::from_utf8(left).unwrap());
+            out.push_str(&String::from_utf8(right).unwrap());
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// `size` pairs of section assignments.
+pub fn day04<R: Rng>(rng: &mut R, size: usize) -> String {
+    let mut out = String::new();
+    for _ in 0..size.max(1) {
+        let mut range = || {
+            let start = rng.random_range(1..=99);
+            (start, rng.random_range(start..=99))
+        };
+        let (a, b) = (range(), range());
+        writeln!(out, "{}-{},{}-{}", a.0, a.1, b.0, b.1).unwrap();
+    }
+    out
+}
+
+/// Up to nine stacks holding `size` crates between them, then `size` moves that are all
+/// possible with either crane.
+pub fn day05<R: Rng>(rng: &mut R, size: usize) -> String {
+    let count = rng.random_range(3..=9);
+    let mut stacks = vec![vec![]; count];
+    for _ in 0..size.max(1) {
+        stacks[rng.random_range(0..count)].push(rng.random_range('A'..='Z'));
+    }
+
+    let height = stacks.iter().map(|stack| stack.len()).max().unwrap();
+    let mut out = String::new();
+    for level in (0..height).rev() {
+        let line = stacks
+            .iter()
+            .map(|stack| match stack.get(level) {
+                Some(c) => format!("[{}]", c),
+                None => "   ".to_string(),
+            })
+            .collect::<Vec<_>>();
+        writeln!(out, "{}", line.join(" ")).unwrap();
+    }
+    let labels = (1..=count).map(|i| format!(" {} ", i)).collect::<Vec<_>>();
+    writeln!(out, "{}\n", labels.join(" ")).unwrap();
+
+    // Moving the crates around keeps the stack heights right for checking the next move
+    let mut heights = stacks.iter().map(|stack| stack.len()).collect::<Vec<_>>();
+    for _ in 0..size.max(1) {
+        let from = loop {
+            let from = rng.random_range(0..count);
+            if heights[from] > 0 {
+                break from;
+            }
+        };
+        let to = (from + rng.random_range(1..count)) % count;
+        let moved = rng.random_range(1..=heights[from]);
+        heights[from] -= moved;
+        heights[to] += moved;
+        writeln!(out, "move {} from {} to {}", moved, from + 1, to + 1).unwrap();
+    }
+    out
+}
+
+/// A signal of at least `size` bytes with both kinds of marker somewhere after the start.
+pub fn day06<R: Rng>(rng: &mut R, size: usize) -> String {
+    let len = size.max(40);
+    // Noise from three letters can never look like a marker
+    let mut signal = (0..len)
+        .map(|_| rng.random_range('a'..='c'))
+        .collect::<Vec<_>>();
+
+    let mut rare = ('d'..='z').collect::<Vec<_>>();
+    for (window, after) in [(4, len / 4), (14, len / 2)] {
+        rare.shuffle(rng);
+        let at = rng.random_range(after..len - window);
+        signal[at..at + window].copy_from_slice(&rare[..window]);
+    }
+    signal.into_iter().chain(['\n']).collect()
+}
+
+fn name<R: Rng>(rng: &mut R) -> String {
+    let mut name = (0..rng.random_range(1..=8))
+        .map(|_| lowercase(rng))
+        .collect::<String>();
+    if rng.random_bool(0.4) {
+        name.push('.');
+        name.extend((0..3).map(|_| lowercase(rng)));
+    }
+    name
+}
+
+struct Entry {
+    name: String,
+    depth: usize,
+    // None for directories
+    size: Option<u32>,
+    children: Vec<usize>,
+}
+
+/// A terminal session listing every directory of a tree with `size` files and directories.
+pub fn day07<R: Rng>(rng: &mut R, size: usize) -> String {
+    let mut tree = vec![Entry {
+        name: "/".to_string(),
+        depth: 0,
+        size: None,
+        children: vec![],
+    }];
+    let mut dirs = vec![0];
+
+    // Each new entry goes in a random existing directory, so the tree fills out in breadth and
+    // depth rather than dying out early
+    while tree.len() <= size {
+        let parent = *dirs.choose(rng).unwrap();
+        let name = name(rng);
+        if tree[parent]
+            .children
+            .iter()
+            .any(|&child| tree[child].name == name)
+        {
+            continue;
+        }
+
+        let depth = tree[parent].depth + 1;
+        let is_dir = depth < 10 && rng.random_bool(0.25);
+        // Mostly small files with the odd big one, like a real disk
+        let size = (!is_dir).then(|| {
+            if rng.random_bool(0.75) {
+                rng.random_range(1..=400_000)
+            } else {
+                rng.random_range(1..=10_000)
+            }
+        });
+        let index = tree.len();
+        tree.push(Entry {
+            name,
+            depth,
+            size,
+            children: vec![],
+        });
+        tree[parent].children.push(index);
+        if is_dir {
+            dirs.push(index);
+        }
+    }
+
+    let mut out = String::from("$ cd /\n");
+    list_dir(&tree, 0, &mut out);
+    out
+}
+
+fn list_dir(tree: &[Entry], dir: usize, out: &mut String) {
+    out.push_str("$ ls\n");
+    for &child in tree[dir].children.iter() {
+        match tree[child].size {
+            Some(size) => writeln!(out, "{} {}", size, tree[child].name).unwrap(),
+            None => writeln!(out, "dir {}", tree[child].name).unwrap(),
+        }
+    }
+
+    for &child in tree[dir].children.iter() {
+        if tree[child].size.is_none() {
+            writeln!(out, "$ cd {}", tree[child].name).unwrap();
+            list_dir(tree, child, out);
+            out.push_str("$ cd ..\n");
+        }
+    }
+}
+
+/// A `size` x `size` forest.
+pub fn day08<R: Rng>(rng: &mut R, size: usize) -> String {
+    let size = size.max(1);
+    (0..size)
+        .map(|_| {
+            (0..size)
+                .map(|_| rng.random_range('0'..='9'))
+                .chain(['\n'])
+                .collect::<String>()
+        })
+        .collect()
+}
+
+/// `size` motions of the head of the rope, about one in five of them diagonal.
+pub fn day09<R: Rng>(rng: &mut R, size: usize) -> String {
+    (0..size.max(1))
+        .map(|_| {
+            let dirs = if rng.random_bool(0.2) {
+                ["UR", "UL", "DR", "DL"]
+            } else {
+                ["R", "L", "U", "D"]
+            };
+            let dir = dirs.choose(rng).unwrap();
+            format!("{} {}\n", dir, rng.random_range(1..=20))
+        })
+        .collect()
+}
+
+/// A program of at least `size` instructions that runs for the whole of the CRT's 240 cycles.
+pub fn day10<R: Rng>(rng: &mut R, size: usize) -> String {
+    let mut out = String::new();
+    let (mut instructions, mut cycles) = (0, 0);
+    while instructions < size || cycles < 240 {
+        if rng.random_bool(0.4) {
+            out.push_str("noop\n");
+            cycles += 1;
+        } else {
+            writeln!(out, "addx {}", rng.random_range(-20..=20)).unwrap();
+            cycles += 2;
+        }
+        instructions += 1;
+    }
+    out
+}
+
+const PRIMES: [u32; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+enum MonkeyOp {
+    Square,
+    Mul(u64),
+    Add(u64),
+}
+
+struct MonkeySpec {
+    items: Vec<u64>,
+    op: MonkeyOp,
+    divisor: u64,
+    targets: (usize, usize),
+}
+
+/*
+Part 1 can't keep worry levels down with modular arithmetic, so a set of monkeys that squares
+its way past u64 in 20 rounds has no answer the puzzle would accept. Monkeys are rolled until
+part 1 plays out without overflowing. With only two monkeys every item is back with the squarer
+every other round, which always overflows, so there are at least three.
+*/
+/// `size` monkeys (at least three, at most twelve) each testing a different prime and never
+/// throwing to themselves.
+pub fn day11<R: Rng>(rng: &mut R, size: usize) -> String {
+    let count = size.clamp(3, PRIMES.len());
+    let monkeys = loop {
+        let monkeys = roll_monkeys(rng, count);
+        if part1_fits(&monkeys) {
+            break monkeys;
+        }
+    };
+
+    let monkeys = monkeys
+        .iter()
+        .enumerate()
+        .map(|(i, monkey)| {
+            let items = monkey
+                .items
+                .iter()
+                .map(|item| item.to_string())
+                .collect::<Vec<_>>();
+            let operation = match monkey.op {
+                MonkeyOp::Square => "old * old".to_string(),
+                MonkeyOp::Mul(n) => format!("old * {}", n),
+                MonkeyOp::Add(n) => format!("old + {}", n),
+            };
+            format!(
+                "Monkey {}:\n  Starting items: {}\n  Operation: new = {}\n  Test: divisible by {}\n    If true: throw to monkey {}\n    If false: throw to monkey {}\n",
+                i,
+                items.join(", "),
+                operation,
+                monkey.divisor,
+                monkey.targets.0,
+                monkey.targets.1
+            )
+        })
+        .collect::<Vec<_>>();
+    monkeys.join("\n")
+}
+
+fn roll_monkeys<R: Rng>(rng: &mut R, count: usize) -> Vec<MonkeySpec> {
+    let mut primes = PRIMES.to_vec();
+    primes.shuffle(rng);
+
+    // Like the real puzzle, only one monkey squares the worry level
+    let squarer = rng.random_range(0..count);
+    (0..count)
+        .map(|i| {
+            let items = (0..rng.random_range(1..=6))
+                .map(|_| rng.random_range(40..=99))
+                .collect();
+            let op = if i == squarer {
+                MonkeyOp::Square
+            } else if rng.random_bool(0.5) {
+                MonkeyOp::Mul(rng.random_range(2..=19))
+            } else {
+                MonkeyOp::Add(rng.random_range(1..=8))
+            };
+            let mut target = || (i + rng.random_range(1..count)) % count;
+            MonkeySpec {
+                items,
+                op,
+                divisor: primes[i] as u64,
+                targets: (target(), target()),
+            }
+        })
+        .collect()
+}
+
+fn part1_fits(monkeys: &[MonkeySpec]) -> bool {
+    let mut items = monkeys
+        .iter()
+        .map(|monkey| monkey.items.clone())
+        .collect::<Vec<_>>();
+    for _ in 0..20 {
+        for (i, monkey) in monkeys.iter().enumerate() {
+            for old in std::mem::take(&mut items[i]) {
+                let new = match monkey.op {
+                    MonkeyOp::Square => old.checked_mul(old),
+                    MonkeyOp::Mul(n) => old.checked_mul(n),
+                    MonkeyOp::Add(n) => old.checked_add(n),
+                };
+                let Some(new) = new else {
+                    return false;
+                };
+                let new = new / 3;
+                let target = match new % monkey.divisor {
+                    0 => monkey.targets.0,
+                    _ => monkey.targets.1,
+                };
+                items[target].push(new);
+            }
+        }
+    }
+    true
+}
+
+/// A heightmap `size` wide (at least 27, so there's room to climb to 'z') with a route from S
+/// to E winding through random terrain.
+pub fn day12<R: Rng>(rng: &mut R, size: usize) -> String {
+    let cols = size.max(27);
+    let rows = (cols / 4).max(5);
+    let mut grid = (0..rows)
+        .map(|_| (0..cols).map(|_| lowercase(rng)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    // Left to right, wandering up or down within each column
+    let mut route = vec![];
+    let mut row = rng.random_range(0..rows);
+    for col in 0..cols {
+        let target = rng.random_range(0..rows);
+        while row != target {
+            route.push((row, col));
+            row = if target > row { row + 1 } else { row - 1 };
+        }
+        route.push((row, col));
+    }
+
+    // Climbing at most one level per step, from 'a' at S to 'z' at E
+    let last = route.len() - 1;
+    for (i, &(r, c)) in route.iter().enumerate() {
+        grid[r][c] = (b'a' + (25 * i / last) as u8) as char;
+    }
+    let (start, end) = (route[0], route[last]);
+    grid[start.0][start.1] = 'S';
+    grid[end.0][end.1] = 'E';
+
+    grid.iter()
+        .map(|row| row.iter().chain(['\n'].iter()).collect::<String>())
+        .collect()
+}
+
+fn packet<R: Rng>(rng: &mut R, depth: usize) -> String {
+    if depth >= 4 || rng.random_bool(0.4) {
+        return rng.random_range(0..=10).to_string();
+    }
+    packet_list(rng, depth)
+}
+
+fn packet_list<R: Rng>(rng: &mut R, depth: usize) -> String {
+    let items = (0..rng.random_range(0..=4))
+        .map(|_| packet(rng, depth + 1))
+        .collect::<Vec<_>>();
+    format!("[{}]", items.join(","))
+}
+
+/// `size` pairs of packets.
+pub fn day13<R: Rng>(rng: &mut R, size: usize) -> String {
+    let pairs = (0..size.max(1))
+        .map(|_| {
+            // Packets are always lists at the top level
+            let left = packet_list(rng, 0);
+            format!("{}\n{}\n", left, packet_list(rng, 0))
+        })
+        .collect::<Vec<_>>();
+    pairs.join("\n")
+}
+
+/// `size` rock paths of straight segments below the sand source.
+pub fn day14<R: Rng>(rng: &mut R, size: usize) -> String {
+    (0..size.max(1))
+        .map(|_| {
+            let (mut x, mut y) = (
+                rng.random_range(460..=540i32),
+                rng.random_range(12..=150i32),
+            );
+            let mut points = vec![format!("{},{}", x, y)];
+            for segment in 0..rng.random_range(1..=5) {
+                let step = rng.random_range(1..=8) * if rng.random_bool(0.5) { 1 } else { -1 };
+                if segment % 2 == 0 {
+                    x += step;
+                } else {
+                    y = (y + step).clamp(12, 160);
+                }
+                points.push(format!("{},{}", x, y));
+            }
+            points.join(" -> ") + "\n"
+        })
+        .collect()
+}
+
+/// The search area of the real puzzle.
+pub const DAY15_MAX: i64 = 4_000_000;
+
+/*
+One cell inside 0..=max is left uncovered. Every sensor reaches to just short of it, so a sensor
+in each corner of the search area covers its whole quadrant, and the rest are scattered around
+for variety. Each sensor's beacon is then the neighbour of the hidden cell facing it, which is
+never further away than any other beacon.
+*/
+/// `size` sensors or more, leaving exactly one cell in 0..=`max` (at least 4) uncovered.
+pub fn day15<R: Rng>(rng: &mut R, size: usize, max: i64) -> String {
+    let max = max.max(4);
+    let hidden = (rng.random_range(1..max), rng.random_range(1..max));
+
+    let mut sensors = vec![(0, 0), (0, max), (max, 0), (max, max)];
+    while sensors.len() < size.max(4) {
+        let sensor = (rng.random_range(0..=max), rng.random_range(0..=max));
+        if (sensor.0 - hidden.0).abs() + (sensor.1 - hidden.1).abs() >= 2 {
+            sensors.push(sensor);
+        }
+    }
+    sensors.shuffle(rng);
+
+    sensors
+        .iter()
+        .map(|&(x, y)| {
+            let beacon = if x != hidden.0 {
+                (hidden.0 + (x - hidden.0).signum(), hidden.1)
+            } else {
+                (hidden.0, hidden.1 + (y - hidden.1).signum())
+            };
+            format!(
+                "Sensor at x={}, y={}: closest beacon is at x={}, y={}\n",
+                x, y, beacon.0, beacon.1
+            )
+        })
+        .collect()
+}
+
+/// `size` valves (at least two) in a connected network, up to fifteen of them worth opening.
+pub fn day16<R: Rng>(rng: &mut R, size: usize) -> String {
+    let count = size.clamp(2, 26 * 26);
+    let mut labels = vec!["AA".to_string()];
+    let mut seen = HashSet::from(["AA".to_string()]);
+    while labels.len() < count {
+        let label = format!(
+            "{}{}",
+            rng.random_range('A'..='Z'),
+            rng.random_range('A'..='Z')
+        );
+        if seen.insert(label.clone()) {
+            labels.push(label);
+        }
+    }
+
+    let mut rates = vec![0; count];
+    let mut useful = (1..count).collect::<Vec<_>>();
+    useful.shuffle(rng);
+    for &valve in useful.iter().take(15) {
+        rates[valve] = rng.random_range(1..=25);
+    }
+
+    // A random spanning tree keeps everything reachable, plus a few shortcuts
+    let mut tunnels = vec![BTreeSet::new(); count];
+    let mut link = |a: usize, b: usize| {
+        if a != b {
+            tunnels[a].insert(b);
+            tunnels[b].insert(a);
+        }
+    };
+    for valve in 1..count {
+        link(valve, rng.random_range(0..valve));
+    }
+    for _ in 0..count / 2 {
+        link(rng.random_range(0..count), rng.random_range(0..count));
+    }
+
+    let mut order = (0..count).collect::<Vec<_>>();
+    order.shuffle(rng);
+    order
+        .iter()
+        .map(|&valve| {
+            let neighbors = tunnels[valve]
+                .iter()
+                .map(|&n| labels[n].as_str())
+                .collect::<Vec<_>>();
+            let tunnels = match neighbors.len() {
+                1 => "tunnel leads to valve",
+                _ => "tunnels lead to valves",
+            };
+            format!(
+                "Valve {} has flow rate={}; {} {}\n",
+                labels[valve],
+                rates[valve],
+                tunnels,
+                neighbors.join(", ")
+            )
+        })
+        .collect()
+}
+
+/// A jet pattern `size` long.
+pub fn day17<R: Rng>(rng: &mut R, size: usize) -> String {
+    (0..size.max(1))
+        .map(|_| if rng.random_bool(0.5) { '<' } else { '>' })
+        .chain(['\n'])
+        .collect()
+}
+
+const DIRECTIONS: [(i32, i32, i32); 6] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+
+/// A lumpy droplet of `size` cubes grown from the origin, so it has negative coordinates and
+/// usually some air pockets.
+pub fn day18<R: Rng>(rng: &mut R, size: usize) -> String {
+    let mut cubes = vec![(0, 0, 0)];
+    let mut seen = HashSet::from([(0, 0, 0)]);
+    while cubes.len() < size.max(1) {
+        let (x, y, z) = *cubes.choose(rng).unwrap();
+        let (dx, dy, dz) = *DIRECTIONS.choose(rng).unwrap();
+        let cube = (x + dx, y + dy, z + dz);
+        if seen.insert(cube) {
+            cubes.push(cube);
+        }
+    }
+    cubes.shuffle(rng);
+    cubes
+        .iter()
+        .map(|(x, y, z)| format!("{},{},{}\n", x, y, z))
+        .collect()
+}
+
+/// A valley `size` wide (3 to 120) and a quarter as tall, with no blizzards blowing through
+/// the entrance or exit, and always a way across.
+pub fn day24<R: Rng>(rng: &mut R, size: usize) -> String {
+    let width = size.clamp(3, 120);
+    let height = (width / 4).max(3);
+
+    let valley = loop {
+        let valley = (0..height)
+            .map(|_| {
+                (1..=width)
+                    .map(|col| {
+                        let vertical_ok = col != 1 && col != width;
+                        match rng.random_range(0..10) {
+                            0 => '>',
+                            1 => '<',
+                            2 if vertical_ok => '^',
+                            3 if vertical_ok => 'v',
+                            _ => '.',
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        if can_cross(&valley) {
+            break valley;
+        }
+    };
+
+    let mut out = format!("#.{}\n", "#".repeat(width));
+    for row in valley {
+        writeln!(out, "#{}#", row.into_iter().collect::<String>()).unwrap();
+    }
+    writeln!(out, "{}.#", "#".repeat(width)).unwrap();
+    out
+}
+
+/*
+Whether the trip there, back and there again of part 2 can be made, following every blizzard
+minute by minute. The layout repeats every lcm(width, height) minutes, so a search over (cell,
+minute within that) always ends. The entrance is above the first column and the exit below the
+last.
+*/
+fn can_cross(valley: &[Vec<char>]) -> bool {
+    let (height, width) = (valley.len(), valley[0].len());
+    let cells = height * width;
+    let period = num::integer::lcm(width, height);
+
+    // Where the blizzards are at each minute of the period
+    let mut blocked = vec![vec![false; cells]; period];
+    for (r, row) in valley.iter().enumerate() {
+        for (c, &ch) in row.iter().enumerate() {
+            let (dr, dc) = match ch {
+                '>' => (0, 1),
+                '<' => (0, width - 1),
+                'v' => (1, 0),
+                '^' => (height - 1, 0),
+                _ => continue,
+            };
+            for (t, blocked) in blocked.iter_mut().enumerate() {
+                blocked[(r + dr * t) % height * width + (c + dc * t) % width] = true;
+            }
+        }
+    }
+
+    // Cells are numbered row by row, with the entrance and exit after the valley
+    let (entrance, exit) = (cells, cells + 1);
+    let neighbors = |cell: usize| {
+        let mut next = vec![cell];
+        match cell {
+            _ if cell == entrance => next.push(0),
+            _ if cell == exit => next.push(cells - 1),
+            _ => {
+                let (r, c) = (cell / width, cell % width);
+                next.extend((r > 0).then(|| cell - width));
+                next.extend((r + 1 < height).then_some(cell + width));
+                next.extend((c > 0).then(|| cell - 1));
+                next.extend((c + 1 < width).then_some(cell + 1));
+                next.extend((cell == 0).then_some(entrance));
+                next.extend((cell == cells - 1).then_some(exit));
+            }
+        }
+        next
+    };
+
+    // Earliest arrival at `to` leaving `from` at minute `start`
+    let crossing = |from: usize, to: usize, start: usize| {
+        let mut seen = vec![false; (cells + 2) * period];
+        let mut queue = VecDeque::from([(from, start)]);
+        while let Some((cell, time)) = queue.pop_front() {
+            if cell == to {
+                return Some(time);
+            }
+            let t = (time + 1) % period;
+            for next in neighbors(cell) {
+                let open = next >= cells || !blocked[t][next];
+                if open && !seen[t * (cells + 2) + next] {
+                    seen[t * (cells + 2) + next] = true;
+                    queue.push_back((next, time + 1));
+                }
+            }
+        }
+        None
+    };
+
+    crossing(entrance, exit, 0)
+        .and_then(|time| crossing(exit, entrance, time))
+        .and_then(|time| crossing(entrance, exit, time))
+        .is_some()
+}
+
+fn snafu(mut n: i64) -> String {
+    let mut digits = vec![];
+    while n != 0 {
+        let (c, carry) = match n.rem_euclid(5) {
+            0 => ('0', 0),
+            1 => ('1', 0),
+            2 => ('2', 0),
+            3 => ('=', 1),
+            _ => ('-', 1),
+        };
+        digits.push(c);
+        n = n.div_euclid(5) + carry;
+    }
+    if digits.is_empty() {
+        digits.push('0');
+    }
+    digits.iter().rev().collect()
+}
+
+/// `size` fuel requirements in SNAFU.
+pub fn day25<R: Rng>(rng: &mut R, size: usize) -> String {
+    (0..size.max(1))
+        .map(|_| snafu(rng.random_range(1..=1_000_000_000_000)) + "\n")
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deterministic() {
+        for day in DAYS {
+            let input = generate(day, 7, 30).unwrap();
+            assert!(!input.is_empty());
+            assert_eq!(input, generate(day, 7, 30).unwrap(), "day {}", day);
+        }
+        assert!(generate(19, 7, 30).is_err());
+    }
+
+    #[test]
+    fn test_formats() {
+        let rng = &mut StdRng::seed_from_u64(1);
+        assert!(day05(rng, 20).contains("\n\nmove "));
+        assert_eq!(day08(rng, 5).lines().count(), 5);
+        assert_eq!(day17(rng, 50).trim_end().len(), 50);
+        let diagonal = |line: &str| line.split(' ').next().is_some_and(|dir| dir.len() == 2);
+        let motions = day09(rng, 100);
+        assert!(motions.lines().any(diagonal));
+        assert!(!motions.lines().all(diagonal));
+        assert_eq!(day11(rng, 2).matches("Monkey").count(), 3);
+        assert_eq!(snafu(2022), "1=11-2");
+        assert_eq!(snafu(4890), "2=-1=0");
+
+        let heightmap = day12(rng, 30);
+        assert_eq!(heightmap.matches('S').count(), 1);
+        assert_eq!(heightmap.matches('E').count(), 1);
+
+        let valves = day16(rng, 30);
+        assert_eq!(valves.lines().count(), 30);
+        assert!(valves.contains("Valve AA has flow rate=0;"));
+    }
+}
@@ -0,0 +1,2 @@
+// Code shared between the daily solutions
+pub mod generate;